@@ -0,0 +1,225 @@
+use {
+    bencher::{
+        benchmark_group,
+        benchmark_main,
+        black_box,
+        Bencher,
+    },
+
+    bitwrap::*,
+};
+
+
+#[derive(Debug, Default, PartialEq, BitWrap)]
+struct TsHeader {
+    #[bitfield(8, name = _sync, value = 0x47)]
+    #[bitfield(1)]
+    error: bool,
+    #[bitfield(1)]
+    pusi: bool,
+    #[bitfield(1)]
+    priority: bool,
+    #[bitfield(13)]
+    pid: u16,
+    #[bitfield(2)]
+    scrambling: u8,
+    #[bitfield(2)]
+    adaptation: u8,
+    #[bitfield(4)]
+    cc: u8,
+}
+
+
+#[derive(Debug, Default, PartialEq, BitWrap)]
+struct Pcr {
+    #[bitfield(33)]
+    base: u64,
+    #[bitfield(6, name = _reserved, value = 0x3F)]
+    #[bitfield(9)]
+    extension: u16,
+}
+
+
+const TS: &[u8] = &[0x47, 0x40, 0x11, 0x1A];
+const PCR: &[u8] = &[0x12, 0x34, 0x56, 0x78, 0xFE, 0x21];
+
+
+// byte-by-byte code as it was generated before word-at-a-time loads and stores
+fn pack_ts_bytes(header: &TsHeader, dst: &mut [u8]) -> Result<usize, BitWrapError> {
+    if dst.is_empty() {
+        return Err(BitWrapErrorKind::BufferTooShort { needed: 1 }.into());
+    }
+    dst[0] = 0x47;
+
+    if dst.len() < 2 {
+        return Err(BitWrapErrorKind::BufferTooShort { needed: 2 }.into());
+    }
+    dst[1] = 0;
+    dst[1] |= u8::from(header.error) << 7;
+    dst[1] |= u8::from(header.pusi) << 6;
+    dst[1] |= u8::from(header.priority) << 5;
+
+    if header.pid >> 13 != 0 {
+        return Err(BitWrapErrorKind::InvalidValue.into());
+    }
+    dst[1] |= (header.pid >> 8) as u8;
+    dst[2] = 0;
+    dst[2] |= header.pid as u8;
+
+    if dst.len() < 4 {
+        return Err(BitWrapErrorKind::BufferTooShort { needed: 4 }.into());
+    }
+    dst[3] = 0;
+    if header.scrambling >> 2 != 0 {
+        return Err(BitWrapErrorKind::InvalidValue.into());
+    }
+    dst[3] |= header.scrambling << 6;
+    if header.adaptation >> 2 != 0 {
+        return Err(BitWrapErrorKind::InvalidValue.into());
+    }
+    dst[3] |= header.adaptation << 4;
+    if header.cc >> 4 != 0 {
+        return Err(BitWrapErrorKind::InvalidValue.into());
+    }
+    dst[3] |= header.cc;
+
+    Ok(4)
+}
+
+
+fn unpack_ts_bytes(src: &[u8]) -> Result<TsHeader, BitWrapError> {
+    if src.len() < 4 {
+        return Err(BitWrapErrorKind::BufferTooShort { needed: 4 }.into());
+    }
+
+    Ok(TsHeader {
+        error: src[1] & 0x80 != 0,
+        pusi: src[1] & 0x40 != 0,
+        priority: src[1] & 0x20 != 0,
+        pid: (u16::from(src[1] & 0x1F) << 8) | u16::from(src[2]),
+        scrambling: src[3] >> 6,
+        adaptation: (src[3] >> 4) & 0x03,
+        cc: src[3] & 0x0F,
+    })
+}
+
+
+fn pack_ts_writer(header: &TsHeader, dst: &mut [u8]) -> Result<usize, BitWrapError> {
+    let mut writer = BitWriter::new(dst);
+    writer.write_bits(8, 0x47)?;
+    writer.write_bits(1, u128::from(header.error))?;
+    writer.write_bits(1, u128::from(header.pusi))?;
+    writer.write_bits(1, u128::from(header.priority))?;
+    writer.write_bits(13, u128::from(header.pid))?;
+    writer.write_bits(2, u128::from(header.scrambling))?;
+    writer.write_bits(2, u128::from(header.adaptation))?;
+    writer.write_bits(4, u128::from(header.cc))?;
+    Ok(writer.position() / 8)
+}
+
+
+fn unpack_ts_reader(src: &[u8]) -> Result<TsHeader, BitWrapError> {
+    let mut reader = BitReader::new(src);
+    reader.skip(8)?;
+
+    Ok(TsHeader {
+        error: reader.read_bits(1)? != 0,
+        pusi: reader.read_bits(1)? != 0,
+        priority: reader.read_bits(1)? != 0,
+        pid: reader.read_bits(13)? as u16,
+        scrambling: reader.read_bits(2)? as u8,
+        adaptation: reader.read_bits(2)? as u8,
+        cc: reader.read_bits(4)? as u8,
+    })
+}
+
+
+fn ts_pack_words(bench: &mut Bencher) {
+    let header = TsHeader::unpack_from(TS).unwrap().0;
+    let mut buffer = [0u8; 4];
+
+    bench.iter(|| black_box(&header).pack(black_box(&mut buffer)).unwrap());
+    assert_eq!(buffer, TS);
+}
+
+
+fn ts_pack_bytes(bench: &mut Bencher) {
+    let header = TsHeader::unpack_from(TS).unwrap().0;
+    let mut buffer = [0u8; 4];
+
+    bench.iter(|| pack_ts_bytes(black_box(&header), black_box(&mut buffer)).unwrap());
+    assert_eq!(buffer, TS);
+}
+
+
+fn ts_pack_writer(bench: &mut Bencher) {
+    let header = TsHeader::unpack_from(TS).unwrap().0;
+    let mut buffer = [0u8; 4];
+
+    bench.iter(|| pack_ts_writer(black_box(&header), black_box(&mut buffer)).unwrap());
+    assert_eq!(buffer, TS);
+}
+
+
+fn ts_unpack_words(bench: &mut Bencher) {
+    bench.iter(|| TsHeader::unpack_from(black_box(TS)).unwrap());
+}
+
+
+fn ts_unpack_bytes(bench: &mut Bencher) {
+    assert_eq!(unpack_ts_bytes(TS), Ok(TsHeader::unpack_from(TS).unwrap().0));
+    bench.iter(|| unpack_ts_bytes(black_box(TS)).unwrap());
+}
+
+
+fn ts_unpack_reader(bench: &mut Bencher) {
+    assert_eq!(unpack_ts_reader(TS), Ok(TsHeader::unpack_from(TS).unwrap().0));
+    bench.iter(|| unpack_ts_reader(black_box(TS)).unwrap());
+}
+
+
+fn pcr_pack_words(bench: &mut Bencher) {
+    let pcr = Pcr::unpack_from(PCR).unwrap().0;
+    let mut buffer = [0u8; 6];
+
+    bench.iter(|| black_box(&pcr).pack(black_box(&mut buffer)).unwrap());
+    assert_eq!(buffer, PCR);
+}
+
+
+fn pcr_unpack_words(bench: &mut Bencher) {
+    bench.iter(|| Pcr::unpack_from(black_box(PCR)).unwrap());
+}
+
+
+fn pcr_unpack_reader(bench: &mut Bencher) {
+    bench.iter(|| {
+        let mut reader = BitReader::new(black_box(PCR));
+        let base = reader.read_bits(33).unwrap() as u64;
+        reader.skip(6).unwrap();
+        let extension = reader.read_bits(9).unwrap() as u16;
+        Pcr { base, extension }
+    });
+}
+
+
+benchmark_group!(
+    ts,
+    ts_pack_words,
+    ts_pack_bytes,
+    ts_pack_writer,
+    ts_unpack_words,
+    ts_unpack_bytes,
+    ts_unpack_reader,
+);
+
+
+benchmark_group!(
+    pcr,
+    pcr_pack_words,
+    pcr_unpack_words,
+    pcr_unpack_reader,
+);
+
+
+benchmark_main!(ts, pcr);
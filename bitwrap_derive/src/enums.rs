@@ -0,0 +1,489 @@
+use {
+    proc_macro2::{
+        Ident,
+        Span,
+        TokenStream,
+    },
+    quote::quote,
+
+    crate::{
+        add_bound,
+        bits_type,
+        StructOptions,
+    },
+};
+
+
+pub struct BitWrapEnum {
+    enum_id: Ident,
+    generics: syn::Generics,
+    // variant ident, discriminant or tag value, and type of the nested field
+    variants: Vec<(Ident, u128, Option<syn::Type>)>,
+    // variant to keep unlisted values
+    unknown: Option<Ident>,
+    // type of the tag field in the unknown variant
+    unknown_tag: Option<syn::Type>,
+    // type of the data field in the unknown variant
+    unknown_data: Option<syn::Type>,
+    // variants selected by tag value
+    tagged: bool,
+    // error type for conversions, pack and unpack
+    error: syn::Type,
+}
+
+
+// variant options from the #[bitwrap] attribute
+#[derive(Default)]
+struct VariantOptions {
+    unknown: bool,
+    tag: Option<u128>,
+}
+
+
+// get discriminant value from the integer literal
+fn discriminant_value(expr: &syn::Expr) -> syn::Result<u128> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(v), .. }) => {
+            v.base10_parse::<u128>().map_err(|_| {
+                syn::Error::new_spanned(v, "enum discriminant should be an unsigned integer")
+            })
+        }
+        _ => Err(syn::Error::new_spanned(
+            expr,
+            "enum discriminant should be an integer literal",
+        )),
+    }
+}
+
+
+impl VariantOptions {
+    fn parse(variant: &syn::Variant) -> syn::Result<Self> {
+        let mut options = Self::default();
+
+        for attr in variant.attrs.iter().filter(|v| v.path.is_ident("bitwrap")) {
+            let list = match attr.parse_meta()? {
+                syn::Meta::List(v) => v,
+                v => return Err(syn::Error::new_spanned(
+                    v,
+                    "bitwrap attribute should be a list: #[bitwrap(...)]",
+                )),
+            };
+
+            for item in &list.nested {
+                match item {
+                    syn::NestedMeta::Meta(syn::Meta::Path(v)) if v.is_ident("unknown") => {
+                        options.unknown = true;
+                    }
+                    syn::NestedMeta::Meta(syn::Meta::NameValue(v)) if v.path.is_ident("tag") => {
+                        let tag = match &v.lit {
+                            syn::Lit::Int(v) => v.base10_parse::<u128>().ok(),
+                            _ => None,
+                        };
+                        match tag {
+                            Some(tag) => options.tag = Some(tag),
+                            None => return Err(syn::Error::new_spanned(
+                                &v.lit,
+                                "tag should be an unsigned integer literal",
+                            )),
+                        }
+                    }
+                    v => return Err(syn::Error::new_spanned(
+                        v,
+                        "bitwrap has unexpected option",
+                    )),
+                }
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+
+// get number of the unnamed fields in the variant
+fn variant_fields(variant: &syn::Variant) -> syn::Result<usize> {
+    match &variant.fields {
+        syn::Fields::Unit => Ok(0),
+        syn::Fields::Unnamed(v) => Ok(v.unnamed.len()),
+        syn::Fields::Named(v) => Err(syn::Error::new_spanned(
+            v,
+            "enum variant should not have named fields",
+        )),
+    }
+}
+
+
+impl BitWrapEnum {
+    pub fn new(ident: &Ident, generics: &syn::Generics, attrs: &[syn::Attribute], data: &syn::DataEnum) -> syn::Result<Self> {
+        let error = StructOptions::parse(attrs, &["error"])?.error;
+        let mut generics = generics.clone();
+        let mut variants = Vec::new();
+        let mut unknown = None;
+        let mut unknown_tag = None;
+        let mut unknown_data = None;
+        let mut next: u128 = 0;
+
+        let mut tagged = false;
+        for variant in &data.variants {
+            tagged |= VariantOptions::parse(variant)?.tag.is_some();
+        }
+
+        for variant in &data.variants {
+            let options = VariantOptions::parse(variant)?;
+            let fields = variant_fields(variant)?;
+
+            for field in variant.fields.iter().skip(if options.unknown { 1 } else { 0 }) {
+                let field_ty = &field.ty;
+                add_bound(&mut generics, field_ty, quote! {
+                    #field_ty: bitwrap::BitWrapExt
+                });
+                add_bound(&mut generics, field_ty, quote! {
+                    #error: From<<#field_ty as bitwrap::BitWrapExt>::Error>
+                });
+            }
+
+            if options.unknown {
+                if tagged && fields != 2 {
+                    return Err(syn::Error::new_spanned(
+                        variant,
+                        "unknown variant should have two unnamed fields: tag and data",
+                    ));
+                } else if ! tagged && fields != 1 {
+                    return Err(syn::Error::new_spanned(
+                        variant,
+                        "unknown variant should have one unnamed field",
+                    ));
+                }
+
+                if unknown.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        variant,
+                        "enum should have only one unknown variant",
+                    ));
+                }
+
+                if tagged {
+                    let mut fields = variant.fields.iter().map(|v| v.ty.clone());
+                    unknown_tag = fields.next();
+                    unknown_data = fields.next();
+                }
+
+                unknown = Some(variant.ident.clone());
+                continue;
+            }
+
+            let value = if tagged {
+                if fields > 1 {
+                    return Err(syn::Error::new_spanned(
+                        &variant.fields,
+                        "enum variant should have one unnamed field",
+                    ));
+                }
+
+                match options.tag {
+                    Some(v) => v,
+                    None => return Err(syn::Error::new_spanned(
+                        variant,
+                        "tag is required for enum variant: #[bitwrap(tag = ...)]",
+                    )),
+                }
+            } else {
+                if fields != 0 {
+                    return Err(syn::Error::new_spanned(
+                        &variant.fields,
+                        "enum variant should not have fields",
+                    ));
+                }
+
+                match &variant.discriminant {
+                    Some((_, expr)) => discriminant_value(expr)?,
+                    None => next,
+                }
+            };
+
+            // unpack selects the first variant with the tag
+            if tagged && variants.iter().any(|(_, v, _)| *v == value) {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    format!("tag {:#04x} is already used by another variant", value),
+                ));
+            }
+
+            next = value.wrapping_add(1);
+            let nested = variant.fields.iter().next().map(|v| v.ty.clone());
+            variants.push((variant.ident.clone(), value, nested));
+        }
+
+        Ok(Self {
+            enum_id: ident.clone(),
+            generics,
+            variants,
+            unknown,
+            unknown_tag,
+            unknown_data,
+            tagged,
+            error,
+        })
+    }
+
+    // error for values without variant
+    fn invalid_value(&self) -> TokenStream {
+        let error = &self.error;
+        quote! {
+            <#error as From<bitwrap::BitWrapError>>::from(
+                bitwrap::BitWrapError::new(bitwrap::BitWrapErrorKind::InvalidValue)
+            )
+        }
+    }
+
+    // implement TryFrom conversions between enum and one of the bits types
+    fn build_type(&self, ty_bits: usize) -> TokenStream {
+        let enum_id = &self.enum_id;
+        let ty = bits_type(ty_bits);
+        let error = &self.error;
+        let invalid = self.invalid_value();
+
+        let mut from_list = TokenStream::new();
+        let mut into_list = TokenStream::new();
+
+        for (ident, value, _) in &self.variants {
+            if ty_bits < 128 && *value >> ty_bits != 0 {
+                // discriminant does not fit into the bits type
+                into_list.extend(quote! {
+                    #enum_id::#ident => Err(#invalid),
+                });
+                continue;
+            }
+
+            let value = syn::LitInt::new(&format!("{}{}", value, ty), Span::call_site());
+
+            from_list.extend(quote! {
+                #value => Ok(#enum_id::#ident),
+            });
+
+            into_list.extend(quote! {
+                #enum_id::#ident => Ok(#value),
+            });
+        }
+
+        // unlisted values kept as is or rejected
+        let from_unknown = if let Some(ident) = &self.unknown {
+            into_list.extend(quote! {
+                #enum_id::#ident(v) => core::convert::TryFrom::try_from(v)
+                    .map_err(|_| #invalid),
+            });
+
+            quote! {
+                v => core::convert::TryFrom::try_from(v)
+                    .map(#enum_id::#ident)
+                    .map_err(|_| #invalid),
+            }
+        } else {
+            quote! {
+                _ => Err(#invalid),
+            }
+        };
+
+        quote! {
+            impl core::convert::TryFrom<#ty> for #enum_id {
+                type Error = #error;
+
+                fn try_from(value: #ty) -> Result<Self, Self::Error> {
+                    match value {
+                        #from_list
+                        #from_unknown
+                    }
+                }
+            }
+
+            impl core::convert::TryFrom<#enum_id> for #ty {
+                type Error = #error;
+
+                fn try_from(value: #enum_id) -> Result<Self, Self::Error> {
+                    match value {
+                        #into_list
+                    }
+                }
+            }
+        }
+    }
+
+    // implement BitWrapExt for nested fields and select variant by tag
+    fn build_tagged(&self) -> TokenStream {
+        let enum_id = &self.enum_id;
+        let error = &self.error;
+        let invalid = self.invalid_value();
+
+        let mut pack_list = TokenStream::new();
+        let mut len_list = TokenStream::new();
+        let mut unpack_list = TokenStream::new();
+        let mut tag_list = TokenStream::new();
+        let mut select_list = TokenStream::new();
+
+        for (ident, value, nested) in &self.variants {
+            let value = syn::LitInt::new(&format!("{}u128", value), Span::call_site());
+
+            if let Some(nested_ty) = nested {
+                pack_list.extend(quote! {
+                    #enum_id::#ident(v) => Ok(bitwrap::BitWrapExt::pack(v, dst)?),
+                });
+
+                len_list.extend(quote! {
+                    #enum_id::#ident(v) => bitwrap::BitWrapExt::packed_len(v),
+                });
+
+                unpack_list.extend(quote! {
+                    #enum_id::#ident(v) => Ok(bitwrap::BitWrapExt::unpack(v, src)?),
+                });
+
+                tag_list.extend(quote! {
+                    #enum_id::#ident(_) => #value,
+                });
+
+                select_list.extend(quote! {
+                    #value => {
+                        let (v, len) = <#nested_ty as bitwrap::BitWrapExt>::unpack_from(src)?;
+                        Ok((#enum_id::#ident(v), len))
+                    }
+                });
+            } else {
+                pack_list.extend(quote! {
+                    #enum_id::#ident => Ok(0),
+                });
+
+                len_list.extend(quote! {
+                    #enum_id::#ident => 0,
+                });
+
+                unpack_list.extend(quote! {
+                    #enum_id::#ident => Ok(0),
+                });
+
+                tag_list.extend(quote! {
+                    #enum_id::#ident => #value,
+                });
+
+                select_list.extend(quote! {
+                    #value => Ok((#enum_id::#ident, 0)),
+                });
+            }
+        }
+
+        // unlisted tags kept with data as is or rejected
+        if let Some(ident) = &self.unknown {
+            let tag_ty = &self.unknown_tag;
+            let data_ty = &self.unknown_data;
+
+            pack_list.extend(quote! {
+                #enum_id::#ident(_, v) => Ok(bitwrap::BitWrapExt::pack(v, dst)?),
+            });
+
+            len_list.extend(quote! {
+                #enum_id::#ident(_, v) => bitwrap::BitWrapExt::packed_len(v),
+            });
+
+            unpack_list.extend(quote! {
+                #enum_id::#ident(_, v) => Ok(bitwrap::BitWrapExt::unpack(v, src)?),
+            });
+
+            tag_list.extend(quote! {
+                #enum_id::#ident(tag, _) => {
+                    <u128 as core::convert::TryFrom<#tag_ty>>::try_from(*tag)
+                        .map_err(|_| #invalid)?
+                }
+            });
+
+            select_list.extend(quote! {
+                tag => {
+                    let tag = <#tag_ty as core::convert::TryFrom<u128>>::try_from(tag)
+                        .map_err(|_| #invalid)?;
+                    let (v, len) = <#data_ty as bitwrap::BitWrapExt>::unpack_from(src)?;
+                    Ok((#enum_id::#ident(tag, v), len))
+                }
+            });
+        } else {
+            select_list.extend(quote! {
+                _ => Err(#invalid),
+            });
+        }
+
+        let (impl_generics, ty_generics, where_clause) = self.generics.split_for_impl();
+
+        // generic parameter for the tag type
+        let mut tag_generics = self.generics.clone();
+        tag_generics.params.push(syn::parse_quote! { BitWrapTagT });
+        let predicates = &mut tag_generics.make_where_clause().predicates;
+        predicates.push(syn::parse_quote! { BitWrapTagT: core::convert::TryFrom<u128> });
+        predicates.push(syn::parse_quote! { u128: core::convert::TryFrom<BitWrapTagT> });
+        let (tag_impl_generics, _, tag_where_clause) = tag_generics.split_for_impl();
+
+        quote! {
+            impl #impl_generics bitwrap::BitWrapExt for #enum_id #ty_generics #where_clause {
+                type Error = #error;
+
+                fn pack(&self, dst: &mut [u8]) -> Result<usize, Self::Error> {
+                    match self {
+                        #pack_list
+                    }
+                }
+
+                fn packed_len(&self) -> usize {
+                    match self {
+                        #len_list
+                    }
+                }
+
+                fn unpack(&mut self, src: &[u8]) -> Result<usize, Self::Error> {
+                    match self {
+                        #unpack_list
+                    }
+                }
+
+                // variant could be selected only with tag
+                fn unpack_from(_src: &[u8]) -> Result<(Self, usize), Self::Error> {
+                    Err(#invalid)
+                }
+            }
+
+            impl #tag_impl_generics bitwrap::BitWrapTag<BitWrapTagT> for #enum_id #ty_generics #tag_where_clause {
+                fn tag(&self) -> Result<BitWrapTagT, Self::Error> {
+                    let tag: u128 = match self {
+                        #tag_list
+                    };
+                    <BitWrapTagT as core::convert::TryFrom<u128>>::try_from(tag)
+                        .map_err(|_| #invalid)
+                }
+
+                fn unpack_tag(&mut self, tag: BitWrapTagT, src: &[u8]) -> Result<usize, Self::Error> {
+                    let (value, len) = <Self as bitwrap::BitWrapTag<BitWrapTagT>>::unpack_tag_from(tag, src)?;
+                    *self = value;
+                    Ok(len)
+                }
+
+                fn unpack_tag_from(tag: BitWrapTagT, src: &[u8]) -> Result<(Self, usize), Self::Error> {
+                    let tag = <u128 as core::convert::TryFrom<BitWrapTagT>>::try_from(tag)
+                        .map_err(|_| #invalid)?;
+                    match tag {
+                        #select_list
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn build(&self) -> TokenStream {
+        if self.tagged {
+            return self.build_tagged();
+        }
+
+        let mut result = TokenStream::new();
+
+        // field type depends on the bitfield size so the conversion is
+        // implemented for each type that bits_type() may choose
+        for ty_bits in &[8, 16, 32, 64, 128] {
+            result.extend(self.build_type(*ty_bits));
+        }
+
+        result
+    }
+}
@@ -1,47 +1,824 @@
 extern crate proc_macro;
 
+mod enums;
+
 use {
     proc_macro2::{
         Ident,
-        Literal,
+        Span,
         TokenStream,
         TokenTree,
-        token_stream::IntoIter,
     },
-    quote::quote,
+    quote::{
+        quote,
+        quote_spanned,
+    },
     syn::{
         self,
         parse_macro_input,
+        ext::IdentExt,
+        parse::ParseStream,
+        spanned::Spanned,
+        Token,
     },
+
+    crate::enums::BitWrapEnum,
 };
 
 
 struct BitWrapMacro {
     struct_id: Ident,
+    generics: syn::Generics,
     pack_list: TokenStream,
     unpack_list: TokenStream,
+    // pack and unpack for the fixed-size array without errors
+    to_bytes_list: TokenStream,
+    from_bytes_list: TokenStream,
+    // all fields could be converted without errors
+    infallible: bool,
+    // packed size of the variable-length fields
+    packed_len_list: TokenStream,
+    // code to set length field after covered fields are packed
+    patches: Vec<(String, TokenStream)>,
+    bits: usize,
+    // bits of the fixed-size fields from the struct begin
+    offset_bits: usize,
+    // variable-length fields before the current field
+    dynamic: bool,
+    // struct fields
+    fields: Vec<Ident>,
+    // fields with values extracted on unpack
+    unpacked: Vec<syn::Member>,
+    // tag name and field with enum selected by tag
+    tags: Vec<(String, syn::Member, syn::Type)>,
+    // count name and field with collection
+    counts: Vec<(String, syn::Member, syn::Type)>,
+    // length name and field with unit or adjust in the length expression
+    lengths: Vec<(String, Bitfield, syn::Type)>,
+    // value of the current field on pack if differs from `self.field`
+    access: Option<TokenStream>,
+    // compile-time checks for the nested field sizes
+    asserts: TokenStream,
+    // fields left in the run of fixed-size fields with one buffer check
+    run_fields: usize,
+    // words of the current run: number of fields and size in bytes
+    words: Vec<(usize, usize)>,
+    // bits of the fields in the current word
+    word_bits: usize,
+    // struct options from the #[bitwrap] attribute
+    options: StructOptions,
+}
+
+
+// byte order of the multi-byte fields
+#[derive(Clone, Copy, PartialEq)]
+enum Endian {
+    Big,
+    Little,
+}
+
+
+impl Endian {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        match ident.to_string().as_str() {
+            "big" => Ok(Endian::Big),
+            "little" => Ok(Endian::Little),
+            _ => Err(syn::Error::new(ident.span(), "endian should be `big` or `little`")),
+        }
+    }
+}
+
+
+// order of the bits in byte
+#[derive(Clone, Copy, PartialEq)]
+enum BitOrder {
+    // first field starts from the most significant bit
+    Msb0,
+    // first field starts from the least significant bit
+    Lsb0,
+}
+
+
+impl BitOrder {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        match ident.to_string().as_str() {
+            "msb0" => Ok(BitOrder::Msb0),
+            "lsb0" => Ok(BitOrder::Lsb0),
+            _ => Err(syn::Error::new(ident.span(), "bit_order should be `msb0` or `lsb0`")),
+        }
+    }
+}
+
+
+// encoding of the signed integers
+#[derive(Clone, Copy, PartialEq)]
+enum Signed {
+    TwosComplement,
+    SignMagnitude,
+    OnesComplement,
+}
+
+
+impl Signed {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        match ident.to_string().as_str() {
+            "twos_complement" => Ok(Signed::TwosComplement),
+            "sign_magnitude" => Ok(Signed::SignMagnitude),
+            "ones_complement" => Ok(Signed::OnesComplement),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                "sign should be `twos_complement`, `sign_magnitude` or `ones_complement`",
+            )),
+        }
+    }
+}
+
+
+// unit of the length expression or length field
+#[derive(Clone, Copy, PartialEq)]
+enum Unit {
+    // number of bytes in the unit
+    Bytes(usize),
+    Bits,
+    // packed size of the Vec item
+    Elements,
+}
+
+
+impl Unit {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(syn::LitInt) {
+            let lit: syn::LitInt = input.parse()?;
+            return match lit.base10_parse::<usize>() {
+                Ok(v) if v > 0 => Ok(Unit::Bytes(v)),
+                _ => Err(syn::Error::new(lit.span(), "unit size should be a positive number of bytes")),
+            };
+        }
+
+        let ident: Ident = input.parse()?;
+        match ident.to_string().as_str() {
+            "bytes" => Ok(Unit::Bytes(1)),
+            "bits" => Ok(Unit::Bits),
+            "words16" => Ok(Unit::Bytes(2)),
+            "words32" => Ok(Unit::Bytes(4)),
+            "elements" => Ok(Unit::Elements),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                "unit should be `bytes`, `bits`, `words16`, `words32`, `elements` or number of bytes",
+            )),
+        }
+    }
+}
+
+
+// struct options from the #[bitwrap] attribute
+struct StructOptions {
+    // default byte order for the struct fields
+    endian: Endian,
+    // order of the fields in byte
+    bit_order: BitOrder,
+    // error type for pack and unpack
+    error: syn::Type,
+    // expected size in bytes
+    size: Option<syn::LitInt>,
+    // struct with any number of bits nested into another struct
+    bits: Option<Ident>,
+}
+
+
+impl StructOptions {
+    // parse #[bitwrap] attributes with options allowed for struct or enum
+    fn parse(attrs: &[syn::Attribute], allowed: &[&str]) -> syn::Result<Self> {
+        let mut options = Self {
+            endian: Endian::Big,
+            bit_order: BitOrder::Msb0,
+            error: syn::parse_quote! { bitwrap::BitWrapError },
+            size: None,
+            bits: None,
+        };
+
+        for attr in attrs.iter().filter(|v| v.path.is_ident("bitwrap")) {
+            attr.parse_args_with(|input: ParseStream| options.parse_args(input, allowed))?;
+        }
+
+        Ok(options)
+    }
+
+    fn parse_args(&mut self, input: ParseStream, allowed: &[&str]) -> syn::Result<()> {
+        while ! input.is_empty() {
+            let key = input.call(Ident::parse_any)?;
+
+            let name = key.to_string();
+            if ! allowed.contains(&name.as_str()) {
+                return Err(syn::Error::new(
+                    key.span(),
+                    format!("bitwrap has unexpected option `{}`", name),
+                ));
+            }
+
+            // option without value
+            if name == "bits" {
+                self.bits = Some(key);
+            } else {
+                input.parse::<Token![=]>()?;
+            }
+
+            match name.as_str() {
+                "bits" => {}
+                "endian" => self.endian = Endian::parse(input)?,
+                "bit_order" => self.bit_order = BitOrder::parse(input)?,
+                "error" => self.error = input.parse()?,
+                "size" => self.size = Some(input.parse()?),
+                _ => unreachable!(),
+            }
+
+            if ! input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+// bitfield attribute options
+#[derive(Clone)]
+struct Bitfield {
+    // attribute to report errors
+    attr: syn::Attribute,
+    // field size in bits. 0 for nested fields
     bits: usize,
+    // nested field length in bytes
+    limit: TokenStream,
+    // virtual field name
+    name: TokenStream,
+    // virtual field value
+    value: TokenStream,
+    // name of the field with tag to select enum variant
+    tag: Option<Ident>,
+    // name of the field with number of items
+    count: Option<Ident>,
+    // byte order if differs from the struct options
+    endian: Option<Endian>,
+    // encoding of the signed integer field
+    sign: Option<Signed>,
+    // mask value on pack instead of overflow error
+    truncate: bool,
+    // nested object packed at any bit position
+    nested: bool,
+    // compare virtual field with value on unpack
+    check: Option<Check>,
+    // first and last fields of the region with length in the virtual field
+    length_of: Vec<syn::Member>,
+    // unit of the length
+    unit: Option<Unit>,
+    // bytes added to the length in units
+    adjust: TokenStream,
+    // field is packed only if condition is true
+    condition: TokenStream,
+}
+
+
+// unpack mode for virtual fields with value
+#[derive(Clone)]
+enum Check {
+    // return error if value does not match
+    Strict,
+    // set boolean field if value does not match
+    Lenient(syn::Member),
+}
+
+
+// consecutive fields with the same condition
+struct Condition {
+    // condition tokens to compare with the next fields
+    key: String,
+    // attribute to report errors
+    attr: syn::Attribute,
+    // condition with bindings for pack and unpack
+    pack_present: TokenStream,
+    unpack_present: TokenStream,
+    // group position for runtime errors
+    position: TokenStream,
+    // bits of the fixed-size fields before the group
+    offset_bits: usize,
+    // code before the group
+    pack_list: TokenStream,
+    unpack_list: TokenStream,
+    packed_len_list: TokenStream,
+    // fields with Option type
+    members: Vec<syn::Member>,
+}
+
+
+// collect attribute option tokens until the next comma
+fn parse_option_tokens(input: ParseStream, key: &Ident) -> syn::Result<TokenStream> {
+    let mut stream = TokenStream::new();
+
+    while ! input.is_empty() && ! input.peek(Token![,]) {
+        let item: TokenTree = input.parse()?;
+        stream.extend(quote! { #item });
+    }
+
+    if stream.is_empty() {
+        return Err(syn::Error::new(
+            key.span(),
+            format!("bitfield option `{}` requires a value", key),
+        ));
+    }
+
+    Ok(stream)
+}
+
+
+// check if input starts with option: `key = value` or flag
+fn is_option(input: ParseStream) -> bool {
+    if ! input.peek(Ident::peek_any) {
+        return false;
+    }
+
+    if input.peek2(Token![=]) {
+        return true;
+    }
+
+    let fork = input.fork();
+    match fork.call(Ident::parse_any) {
+        Ok(key) => {
+            ["truncate", "nested", "check"].iter().any(|v| key == v) &&
+                (fork.is_empty() || fork.peek(Token![,]))
+        }
+        Err(_) => false,
+    }
+}
+
+
+impl Bitfield {
+    fn parse(attr: &syn::Attribute) -> syn::Result<Self> {
+        let mut bitfield = Self {
+            attr: attr.clone(),
+            bits: 0,
+            limit: TokenStream::new(),
+            name: TokenStream::new(),
+            value: TokenStream::new(),
+            tag: None,
+            count: None,
+            endian: None,
+            sign: None,
+            truncate: false,
+            nested: false,
+            check: None,
+            length_of: Vec::new(),
+            unit: None,
+            adjust: TokenStream::new(),
+            condition: TokenStream::new(),
+        };
+
+        // nested bitfield (attribute without arguments)
+        if attr.tokens.is_empty() {
+            return Ok(bitfield);
+        }
+
+        attr.parse_args_with(|input: ParseStream| bitfield.parse_args(input))?;
+
+        if bitfield.bits != 0 && bitfield.tag.is_some() {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "tag could not be used with bits, use length expression or nested field",
+            ));
+        }
+
+        if bitfield.count.is_some() && (bitfield.bits != 0 || bitfield.tag.is_some()) {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "count could not be used with bits or tag",
+            ));
+        }
+
+        if bitfield.name.is_empty() && ! bitfield.value.is_empty() {
+            return Err(syn::Error::new_spanned(
+                &bitfield.value,
+                "value is allowed only for virtual field with name",
+            ));
+        }
+
+        if ! bitfield.length_of.is_empty() {
+            if bitfield.name.is_empty() {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "length_of requires virtual field with name",
+                ));
+            }
+
+            if ! bitfield.value.is_empty() {
+                return Err(syn::Error::new_spanned(
+                    &bitfield.value,
+                    "length_of could not be used with value",
+                ));
+            }
+        }
+
+        if ! bitfield.condition.is_empty() && ! bitfield.length_of.is_empty() {
+            return Err(syn::Error::new_spanned(
+                &bitfield.condition,
+                "length_of could not be used with condition",
+            ));
+        }
+
+        if (bitfield.unit.is_some() || ! bitfield.adjust.is_empty()) &&
+            bitfield.limit.is_empty() &&
+            bitfield.length_of.is_empty()
+        {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "unit and adjust require length expression or length_of",
+            ));
+        }
+
+        if bitfield.unit == Some(Unit::Elements) && bitfield.limit.is_empty() {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "unit elements requires length expression",
+            ));
+        }
+
+        if bitfield.check.is_some() && bitfield.value.is_empty() {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "check requires virtual field with value",
+            ));
+        }
+
+        if bitfield.nested && (bitfield.bits == 0 || ! bitfield.name.is_empty()) {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "nested requires size in bits and could not be used with virtual field",
+            ));
+        }
+
+        if bitfield.truncate && bitfield.bits == 0 {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "truncate requires size in bits",
+            ));
+        }
+
+        if ! bitfield.name.is_empty() && bitfield.bits == 0 {
+            return Err(syn::Error::new_spanned(
+                &bitfield.name,
+                "virtual field requires size in bits",
+            ));
+        }
+
+        Ok(bitfield)
+    }
+
+    fn parse_args(&mut self, input: ParseStream) -> syn::Result<()> {
+        if input.is_empty() {
+            return Ok(());
+        }
+
+        // first argument: size in bits, length expression or option
+        let mut first = true;
+
+        if input.peek(syn::LitInt) {
+            let lit: syn::LitInt = input.parse()?;
+            match lit.base10_parse::<usize>() {
+                Ok(bits) if bits > 0 && bits <= 128 => self.bits = bits,
+                _ => return Err(syn::Error::new(
+                    lit.span(),
+                    "bitfield size should be a number in range 1 ..= 128",
+                )),
+            }
+            first = false;
+        } else if ! is_option(input) {
+            // expression without '=' is a field length
+            let key = Ident::new("length", input.span());
+            self.limit = parse_option_tokens(input, &key)?;
+            first = false;
+        }
+
+        // parse options
+        while ! input.is_empty() {
+            if ! first {
+                input.parse::<Token![,]>()?;
+                if input.is_empty() {
+                    break;
+                }
+            }
+            first = false;
+
+            let key = input.call(Ident::parse_any)?;
+
+            // flag options without value
+            if key == "truncate" {
+                self.truncate = true;
+                continue;
+            }
+
+            if key == "nested" {
+                self.nested = true;
+                continue;
+            }
+
+            if key == "check" {
+                if input.peek(Token![=]) {
+                    input.parse::<Token![=]>()?;
+                    self.check = Some(Check::Lenient(input.parse()?));
+                } else {
+                    self.check = Some(Check::Strict);
+                }
+                continue;
+            }
+
+            input.parse::<Token![=]>()?;
+
+            match key.to_string().as_str() {
+                "name" => self.name = parse_option_tokens(input, &key)?,
+                "value" => self.value = parse_option_tokens(input, &key)?,
+                "tag" => {
+                    let tag: Ident = input.parse().map_err(|e| {
+                        syn::Error::new(e.span(), "tag should be a field name")
+                    })?;
+                    self.tag = Some(tag);
+                }
+                "count" => {
+                    let count: Ident = input.parse().map_err(|e| {
+                        syn::Error::new(e.span(), "count should be a field name")
+                    })?;
+                    self.count = Some(count);
+                }
+                "length_of" => {
+                    if input.peek(syn::token::Paren) {
+                        let content;
+                        syn::parenthesized!(content in input);
+                        let list = content.parse_terminated::<syn::Member, Token![,]>(syn::parse::Parse::parse)?;
+                        self.length_of = list.into_iter().collect();
+                    } else {
+                        self.length_of = vec![input.parse()?];
+                    }
+                }
+                "unit" => self.unit = Some(Unit::parse(input)?),
+                "adjust" => self.adjust = parse_option_tokens(input, &key)?,
+                "if" => self.condition = parse_option_tokens(input, &key)?,
+                "endian" => self.endian = Some(Endian::parse(input)?),
+                "sign" => self.sign = Some(Signed::parse(input)?),
+                v => return Err(syn::Error::new(
+                    key.span(),
+                    format!("bitfield has unexpected option `{}`", v),
+                )),
+            }
+        }
+
+        Ok(())
+    }
 }
 
 
-// convert TokenTree literal to usize
 #[inline]
-fn literal_to_usize(v: &Literal) -> Option<usize> {
-    syn::LitInt::from(v.clone()).base10_parse::<usize>().ok()
+fn is_bool_type(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Path(v) if v.path.is_ident("bool"))
+}
+
+
+// integer literal that could be packed into the bits without error
+fn literal_fits(value: &TokenStream, bits: usize) -> bool {
+    match syn::parse2::<syn::LitInt>(value.clone()).and_then(|v| v.base10_parse::<u128>()) {
+        Ok(v) => bits == 128 || v >> bits == 0,
+        Err(_) => false,
+    }
+}
+
+
+// get size in bits for the signed integer type
+fn signed_type_bits(ty: &syn::Type) -> Option<usize> {
+    let path = match ty {
+        syn::Type::Path(v) => &v.path,
+        _ => return None,
+    };
+
+    [("i8", 8), ("i16", 16), ("i32", 32), ("i64", 64), ("i128", 128)]
+        .iter()
+        .find(|(name, _)| path.is_ident(name))
+        .map(|(_, bits)| *bits)
+}
+
+
+// get size in bits for the unsigned integer type
+fn unsigned_type_bits(ty: &syn::Type) -> Option<usize> {
+    let path = match ty {
+        syn::Type::Path(v) => &v.path,
+        _ => return None,
+    };
+
+    [("u8", 8), ("u16", 16), ("u32", 32), ("u64", 64), ("u128", 128)]
+        .iter()
+        .find(|(name, _)| path.is_ident(name))
+        .map(|(_, bits)| *bits)
+}
+
+
+// signed type with the same size as the bits type
+fn signed_bits_type(ty: &Ident) -> (Ident, usize) {
+    let bits = match ty.to_string().as_str() {
+        "u8" => 8,
+        "u16" => 16,
+        "u32" => 32,
+        "u64" => 64,
+        _ => 128,
+    };
+
+    (Ident::new(&format!("i{}", bits), ty.span()), bits)
+}
+
+
+// builds map_err call for the conversion error type
+type MapErr<'a> = dyn Fn(&TokenStream) -> TokenStream + 'a;
+
+
+// condition for the signed value `v` that does not fit into the field size
+fn signed_overflow(field_ty: &syn::Type, bitfield: &Bitfield) -> Option<TokenStream> {
+    let ty_bits = signed_type_bits(field_ty)?;
+    let bits = bitfield.bits;
+    let twos = bitfield.sign.unwrap_or(Signed::TwosComplement) == Signed::TwosComplement;
+
+    if bits == ty_bits {
+        if twos {
+            None
+        } else {
+            // the minimal value has no positive pair
+            Some(quote! { v == <#field_ty>::MIN })
+        }
+    } else {
+        let max = proc_macro2::Literal::u128_unsuffixed((1u128 << (bits - 1)) - 1);
+        if twos {
+            Some(quote! { ! (- #max - 1 ..= #max).contains(&v) })
+        } else {
+            Some(quote! { ! (- #max ..= #max).contains(&v) })
+        }
+    }
+}
+
+
+// convert field value into the bits type
+fn convert_into(ty: &Ident, field_ty: &syn::Type, value: &TokenStream, bitfield: &Bitfield, map_err: &MapErr) -> TokenStream {
+    if is_bool_type(field_ty) {
+        return quote! { if #value { 1 } else { 0 } };
+    }
+
+    if signed_type_bits(field_ty).is_none() {
+        let map_err = map_err(&quote! { <#ty as core::convert::TryFrom<#field_ty>>::Error });
+        return quote! { <#ty as core::convert::TryFrom<#field_ty>>::try_from(#value) #map_err ? };
+    }
+
+    // bits above the field size are masked
+    let (_, ty_bits) = signed_bits_type(ty);
+    let shift = ty_bits - bitfield.bits;
+
+    let value = match bitfield.sign.unwrap_or(Signed::TwosComplement) {
+        Signed::TwosComplement => quote! { (#value) as #ty },
+        Signed::SignMagnitude => {
+            let sign_shift = bitfield.bits - 1;
+            quote! {{
+                let v: #field_ty = #value;
+                let m = v.unsigned_abs() as #ty;
+                if v < 0 { m | ((1 as #ty) << #sign_shift) } else { m }
+            }}
+        }
+        Signed::OnesComplement => quote! {{
+            let v: #field_ty = #value;
+            if v < 0 { ! (v.unsigned_abs() as #ty) } else { v as #ty }
+        }},
+    };
+
+    quote! { ((#value) & (#ty::MAX >> #shift)) }
 }
 
 
-// push attribute option tokens to TokenStream
-fn extend_token_stream(stream: &mut TokenStream, iter: &mut IntoIter) {
-    for item in iter {
-        match item {
-            TokenTree::Punct(v) if v.as_char() == ',' => break,
-            v => stream.extend(quote! { #v }),
+// convert bits value into the field type
+fn convert_from(ty: &Ident, field_ty: &syn::Type, value: &TokenStream, bitfield: &Bitfield, map_err: &MapErr) -> TokenStream {
+    if is_bool_type(field_ty) {
+        return quote! { #value != 0 };
+    }
+
+    if signed_type_bits(field_ty).is_none() {
+        let map_err = map_err(&quote! { <#field_ty as core::convert::TryFrom<#ty>>::Error });
+        return quote! { <#field_ty as core::convert::TryFrom<#ty>>::try_from(#value) #map_err ? };
+    }
+
+    let (signed_ty, ty_bits) = signed_bits_type(ty);
+    let sign_shift = bitfield.bits - 1;
+
+    match bitfield.sign.unwrap_or(Signed::TwosComplement) {
+        Signed::TwosComplement => {
+            // move sign bit to the top of the signed type and shift back
+            let shift = ty_bits - bitfield.bits;
+            quote! {
+                ((((#value) << #shift) as #signed_ty) >> #shift) as #field_ty
+            }
         }
+        Signed::SignMagnitude => quote! {{
+            let v: #ty = #value;
+            let sign = (1 as #ty) << #sign_shift;
+            let m = (v & ! sign) as #signed_ty;
+            (if v & sign != 0 { - m } else { m }) as #field_ty
+        }},
+        Signed::OnesComplement => {
+            let shift = ty_bits - bitfield.bits;
+            quote! {{
+                let v: #ty = #value;
+                let sign = (1 as #ty) << #sign_shift;
+                if v & sign != 0 {
+                    (- (((! v) & (#ty::MAX >> #shift)) as #signed_ty)) as #field_ty
+                } else {
+                    v as #field_ty
+                }
+            }}
+        }
+    }
+}
+
+
+// check if type depends on the generic type parameters
+fn is_generic_type(ty: &syn::Type, generics: &syn::Generics) -> bool {
+    fn contains(stream: TokenStream, params: &[&Ident]) -> bool {
+        stream.into_iter().any(|item| match item {
+            TokenTree::Ident(v) => params.contains(&&v),
+            TokenTree::Group(v) => contains(v.stream(), params),
+            _ => false,
+        })
+    }
+
+    let params: Vec<&Ident> = generics.type_params().map(|v| &v.ident).collect();
+    ! params.is_empty() && contains(quote! { #ty }, &params)
+}
+
+
+// append where clause for the field type with generic parameters
+fn add_bound(generics: &mut syn::Generics, ty: &syn::Type, bound: TokenStream) {
+    if is_generic_type(ty, generics) {
+        let predicate: syn::WherePredicate = syn::parse_quote! { #bound };
+        generics.make_where_clause().predicates.push(predicate);
+    }
+}
+
+
+// field position after bits are unpacked and offset moved to the end of the field
+fn unpack_position(position: &TokenStream, bits_before: usize, bits: usize) -> TokenStream {
+    let bytes = (bits_before + bits) / 8;
+    if bytes == 0 {
+        position.clone()
+    } else {
+        quote! { (offset - #bytes) * 8 + #bits_before }
+    }
+}
+
+
+// field name for error messages
+fn member_name(member: &syn::Member) -> String {
+    match member {
+        syn::Member::Named(v) => v.to_string(),
+        syn::Member::Unnamed(v) => v.index.to_string(),
+    }
+}
+
+
+// item type of the Vec<T> or Option<T>
+fn wrapped_type<'a>(ty: &'a syn::Type, wrapper: &str) -> Option<&'a syn::Type> {
+    let segment = match ty {
+        syn::Type::Path(v) => v.path.segments.last()?,
+        _ => return None,
+    };
+
+    if segment.ident != wrapper {
+        return None;
+    }
+
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(v) => match v.args.first() {
+            Some(syn::GenericArgument::Type(ty)) => Some(ty),
+            _ => None,
+        },
+        _ => None,
     }
 }
 
 
+// local variable for the region with length in the virtual field
+fn region_local(name: &TokenStream, suffix: &str) -> Ident {
+    Ident::new(&format!("{}_{}", name, suffix), Span::mixed_site())
+}
+
+
+// local variable to keep unpacked field value until struct is built
+fn field_local(member: &syn::Member) -> Ident {
+    Ident::new(&format!("field_{}", member_name(member)), Span::mixed_site())
+}
+
+
 fn bits_type(bits: usize) -> Ident {
     Ident::new(
         if bits <= 8 {
@@ -55,346 +832,1930 @@ fn bits_type(bits: usize) -> Ident {
         } else {
             "u128"
         },
-        proc_macro2::Span::call_site()
+        Span::call_site()
     )
 }
 
 
-impl BitWrapMacro {
-    fn new(ident: &Ident) -> Self {
-        Self {
-            struct_id: ident.clone(),
-            pack_list: TokenStream::default(),
-            unpack_list: TokenStream::default(),
-            bits: 0,
+impl BitWrapMacro {
+    fn new(ident: &Ident, generics: &syn::Generics, attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        Ok(Self {
+            struct_id: ident.clone(),
+            generics: generics.clone(),
+            pack_list: TokenStream::default(),
+            unpack_list: TokenStream::default(),
+            to_bytes_list: TokenStream::default(),
+            from_bytes_list: TokenStream::default(),
+            infallible: true,
+            packed_len_list: TokenStream::default(),
+            patches: Vec::default(),
+            bits: 0,
+            offset_bits: 0,
+            dynamic: false,
+            fields: Vec::default(),
+            unpacked: Vec::default(),
+            tags: Vec::default(),
+            counts: Vec::default(),
+            lengths: Vec::default(),
+            access: None,
+            asserts: TokenStream::default(),
+            run_fields: 0,
+            words: Vec::default(),
+            word_bits: 0,
+            options: StructOptions::parse(attrs, &["endian", "bit_order", "error", "size", "bits"])?,
+        })
+    }
+
+    // bit position of the current field for runtime errors
+    fn position(&self) -> TokenStream {
+        let bit = 8 - self.bits;
+        if bit == 0 {
+            quote! { offset * 8 }
+        } else {
+            quote! { offset * 8 + #bit }
+        }
+    }
+
+    // field value on pack
+    fn access(&self, member: &syn::Member) -> TokenStream {
+        match &self.access {
+            Some(v) => v.clone(),
+            None => quote! { self.#member },
+        }
+    }
+
+    // error with location of the field
+    fn error_at(&self, kind: TokenStream, field: &str, position: &TokenStream) -> TokenStream {
+        let struct_name = self.struct_id.to_string();
+        let error = &self.options.error;
+        quote! {
+            <#error as From<bitwrap::BitWrapError>>::from(
+                bitwrap::BitWrapError::new(bitwrap::BitWrapErrorKind::#kind)
+                    .at_field(#struct_name, #field, #position)
+            )
+        }
+    }
+
+    // add location of the field to the error from conversion or nested field
+    fn map_err_at(&self, field: &str, position: &TokenStream, source: &TokenStream) -> TokenStream {
+        let struct_name = self.struct_id.to_string();
+        let error = &self.options.error;
+        quote! {
+            .map_err(|e| bitwrap::BitWrapErrorExt::at_field(
+                <#error as From<#source>>::from(e),
+                #struct_name,
+                #field,
+                #position,
+            ))
+        }
+    }
+
+    // check that value in the bits type fits into the field size
+    fn overflow_check(&mut self, ty: &Ident, bits: usize, name: &str, position: &TokenStream) {
+        let (_, ty_bits) = signed_bits_type(ty);
+        if bits == ty_bits {
+            return;
+        }
+
+        let error = self.error_at(quote! { InvalidValue }, name, position);
+        self.pack_list.extend(quote! {
+            if value >> #bits != 0 {
+                return Err(#error);
+            }
+        });
+    }
+
+    // check that signed value fits into the field size with selected encoding
+    fn signed_check(&mut self, member: &syn::Member, field_ty: &syn::Type, bitfield: &Bitfield, name: &str, position: &TokenStream) {
+        let condition = match signed_overflow(field_ty, bitfield) {
+            Some(v) => v,
+            None => return,
+        };
+
+        let error = self.error_at(quote! { InvalidValue }, name, position);
+        let access = self.access(member);
+        self.pack_list.extend(quote! {
+            let v: #field_ty = #access;
+            if #condition {
+                return Err(#error);
+            }
+        });
+    }
+
+    // describe current bit offset for error messages
+    fn bit_offset(&self) -> String {
+        if self.dynamic {
+            format!("{} (without variable-length fields)", self.offset_bits)
+        } else {
+            format!("{}", self.offset_bits)
+        }
+    }
+
+    fn assert_align(&self, attr: &syn::Attribute) -> syn::Result<()> {
+        if self.bits == 8 {
+            Ok(())
+        } else {
+            Err(syn::Error::new_spanned(attr, format!(
+                "bitfield should start at byte boundary, current bit offset is {}",
+                self.bit_offset(),
+            )))
+        }
+    }
+
+    // fill bits by chunks up to the end of the current byte.
+    // big-endian takes chunks from the most significant bits of the value,
+    // little-endian takes chunks from the least significant bits.
+    // returns pack code, pack code for zeroed buffer and unpack code
+    fn make_chunks(&self, ty: &Ident, bits: usize, endian: Endian) -> (TokenStream, TokenStream, TokenStream) {
+        let mut pack_list = TokenStream::new();
+        let mut patch_list = TokenStream::new();
+        let mut unpack_list = TokenStream::new();
+
+        let mut free = self.bits;
+        let mut done = 0;
+
+        while done < bits {
+            let size = free.min(bits - done);
+            let mask = 0xFFu8 >> (8 - size);
+
+            // chunk position in the value
+            let value_shift = match endian {
+                Endian::Big => bits - done - size,
+                Endian::Little => done,
+            };
+
+            // chunk position in the byte
+            let byte_shift = match self.options.bit_order {
+                BitOrder::Msb0 => free - size,
+                BitOrder::Lsb0 => 8 - free,
+            };
+
+            let mut chunk = quote! { value };
+            if value_shift != 0 {
+                chunk = quote! { (#chunk >> #value_shift) };
+            }
+            chunk = quote! { ((#chunk as u8) & #mask) };
+            if byte_shift != 0 {
+                chunk = quote! { (#chunk << #byte_shift) };
+            }
+
+            let pack = quote! {
+                dst[offset] |= #chunk;
+            };
+            pack_list.extend(pack.clone());
+            patch_list.extend(pack);
+
+            let mut chunk = quote! { src[offset] };
+            if byte_shift != 0 {
+                chunk = quote! { (#chunk >> #byte_shift) };
+            }
+            chunk = quote! { ((#chunk & #mask) as #ty) };
+            if value_shift != 0 {
+                chunk = quote! { (#chunk << #value_shift) };
+            }
+
+            unpack_list.extend(quote! {
+                value |= #chunk;
+            });
+
+            done += size;
+            free -= size;
+
+            if free == 0 {
+                free = 8;
+
+                let next = quote! {
+                    offset += 1;
+                };
+                pack_list.extend(next.clone());
+                patch_list.extend(next.clone());
+                unpack_list.extend(next);
+
+                if done < bits {
+                    pack_list.extend(quote! {
+                        dst[offset] = 0;
+                    });
+                }
+            }
+        }
+
+        (pack_list, patch_list, unpack_list)
+    }
+
+    fn macro_make_bits(&mut self, ty: &Ident, bits: usize, endian: Endian) {
+        let (pack, patch, unpack) = self.make_chunks(ty, bits, endian);
+
+        self.to_bytes_list.extend(patch);
+
+        let unpack = quote! {
+            let mut value: #ty = 0;
+            #unpack
+        };
+        self.from_bytes_list.extend(unpack.clone());
+
+        if self.words.is_empty() {
+            self.pack_list.extend(pack);
+            self.unpack_list.extend(unpack);
+        } else {
+            self.make_word_bits(ty, bits, endian);
+        }
+
+        self.run_fields = self.run_fields.saturating_sub(1);
+        self.offset_bits += bits;
+        self.bits = 8 - (8 - self.bits + bits) % 8;
+    }
+
+    // put field into the word loaded from the buffer or stored into the buffer
+    // when all fields of the word are packed
+    fn make_word_bits(&mut self, ty: &Ident, bits: usize, endian: Endian) {
+        let (fields, bytes) = self.words[0];
+        let word_ty = bits_type(bytes * 8);
+        let (_, word_size) = signed_bits_type(&word_ty);
+        let word_size = word_size / 8;
+        let word = Ident::new("word", Span::mixed_site());
+        let word_offset = Ident::new("word_offset", Span::mixed_site());
+
+        if self.word_bits == 0 {
+            let skip = word_size - bytes;
+            self.pack_list.extend(quote! {
+                let #word_offset = offset;
+                let mut #word: #word_ty = 0;
+            });
+            self.unpack_list.extend(quote! {
+                let #word: #word_ty = {
+                    let mut v = [0u8; #word_size];
+                    v[#skip ..].copy_from_slice(&src[offset .. offset + #bytes]);
+                    #word_ty::from_be_bytes(v)
+                };
+            });
+        }
+
+        let shift = bytes * 8 - self.word_bits - bits;
+        let (_, ty_bits) = signed_bits_type(ty);
+        let swap = endian == Endian::Little && bits != 8;
+
+        // field value in the word
+        let mut pack = if swap {
+            quote! { (value.swap_bytes() as #word_ty) }
+        } else {
+            quote! { (value as #word_ty) }
+        };
+        let mut unpack = quote! { #word };
+
+        if shift != 0 {
+            unpack = quote! { (#unpack >> #shift) };
+        }
+
+        if bits != ty_bits {
+            let mask = proc_macro2::Literal::u128_unsuffixed(u128::MAX >> (128 - bits));
+            pack = quote! { (#pack & #mask) };
+            unpack = quote! { (#unpack & #mask) };
+        }
+
+        if shift != 0 {
+            pack = quote! { (#pack << #shift) };
+        }
+
+        self.pack_list.extend(quote! {
+            #word |= #pack;
+        });
+
+        let unpack = quote! { (#unpack as #ty) };
+        if swap {
+            self.unpack_list.extend(quote! {
+                let value: #ty = #unpack.swap_bytes();
+            });
+        } else {
+            self.unpack_list.extend(quote! {
+                let value: #ty = #unpack;
+            });
+        }
+
+        let next = (8 - self.bits + bits) / 8;
+        if next != 0 {
+            let next = quote! {
+                offset += #next;
+            };
+            self.pack_list.extend(next.clone());
+            self.unpack_list.extend(next);
+        }
+
+        self.word_bits += bits;
+
+        if fields == 1 {
+            let skip = word_size - bytes;
+            self.pack_list.extend(quote! {
+                dst[#word_offset .. #word_offset + #bytes].copy_from_slice(&#word.to_be_bytes()[#skip ..]);
+            });
+            self.words.remove(0);
+            self.word_bits = 0;
+        } else {
+            self.words[0].0 -= 1;
+        }
+    }
+
+    fn build_bitfield_array(&mut self, member: &syn::Member, field: &syn::Field, bitfield: &Bitfield) -> syn::Result<()> {
+        self.assert_align(&bitfield.attr)?;
+        self.dynamic = true;
+        self.infallible = false;
+
+        let access = self.access(member);
+        self.packed_len_list.extend(quote! {
+            + bitwrap::BitWrapExt::packed_len(&#access)
+        });
+
+        let field_ty = &field.ty;
+        let error = &self.options.error;
+        add_bound(&mut self.generics, field_ty, quote! {
+            #field_ty: bitwrap::BitWrapExt
+        });
+        add_bound(&mut self.generics, field_ty, quote! {
+            #error: From<<#field_ty as bitwrap::BitWrapExt>::Error>
+        });
+
+        let name = member_name(member);
+        let position = self.position();
+        let error = self.error_at(quote! {
+            BufferTooShort { needed: limit - offset }
+        }, &name, &position);
+        let map_err = self.map_err_at(&name, &position, &quote! {
+            <#field_ty as bitwrap::BitWrapExt>::Error
+        });
+
+        self.pack_list.extend(quote! {
+            if dst.len() >= limit {
+                offset += #access.pack(&mut dst[offset .. limit]) #map_err ?;
+            } else {
+                return Err(#error);
+            }
+        });
+
+        let unpack = if let Some(tag) = &bitfield.tag {
+            let tag = self.previous_value(tag);
+            quote! {
+                <#field_ty as bitwrap::BitWrapTag<_>>::unpack_tag_from(
+                    #tag,
+                    &src[offset .. limit],
+                )
+            }
+        } else if let Some(count) = &bitfield.count {
+            let count = self.previous_value(count);
+            quote! {
+                <#field_ty as bitwrap::BitWrapCount>::unpack_count(
+                    ( #count ) as usize,
+                    &src[offset .. limit],
+                )
+            }
+        } else {
+            quote! {
+                <#field_ty as bitwrap::BitWrapExt>::unpack_from(&src[offset .. limit])
+            }
+        };
+
+        let local = field_local(member);
+        self.unpacked.push(member.clone());
+        self.unpack_list.extend(quote! {
+            let #local = if src.len() >= limit {
+                let (v, len) = #unpack #map_err ?;
+                offset += len;
+                v
+            } else {
+                return Err(#error);
+            };
+        });
+
+        Ok(())
+    }
+
+    // get value of the previous field or of the virtual field
+    fn previous_value(&self, name: &Ident) -> TokenStream {
+        if self.fields.iter().any(|v| v == name) {
+            let local = field_local(&syn::Member::Named(name.clone()));
+            quote! { #local }
+        } else {
+            quote! { #name }
+        }
+    }
+
+    // get enum field with tag for the field name
+    fn tag_field(&self, name: &TokenStream) -> Option<(syn::Member, syn::Type)> {
+        let name = name.to_string();
+        self.tags.iter()
+            .find(|(v, _, _)| *v == name)
+            .map(|(_, member, ty)| (member.clone(), ty.clone()))
+    }
+
+    // get tag value for the field from the enum variant
+    fn tag_pack(&mut self, ty: &Ident, body: &syn::Member, body_ty: &syn::Type, name: &str) {
+        add_bound(&mut self.generics, body_ty, quote! {
+            #body_ty: bitwrap::BitWrapTag<#ty>
+        });
+        let error = &self.options.error;
+        add_bound(&mut self.generics, body_ty, quote! {
+            #error: From<<#body_ty as bitwrap::BitWrapExt>::Error>
+        });
+
+        let map_err = self.map_err_at(name, &self.position(), &quote! {
+            <#body_ty as bitwrap::BitWrapExt>::Error
+        });
+        self.pack_list.extend(quote! {
+            let value: #ty = bitwrap::BitWrapTag::<#ty>::tag(&self.#body) #map_err ? ;
+        });
+    }
+
+    // get collection field with count for the field name
+    fn count_field(&self, name: &TokenStream) -> Option<(syn::Member, syn::Type)> {
+        let name = name.to_string();
+        self.counts.iter()
+            .find(|(v, _, _)| *v == name)
+            .map(|(_, member, ty)| (member.clone(), ty.clone()))
+    }
+
+    // get field with length in the virtual field and unit or adjust
+    fn length_field(&self, name: &TokenStream) -> Option<(Bitfield, syn::Type)> {
+        let name = name.to_string();
+        self.lengths.iter()
+            .find(|(v, _, _)| *v == name)
+            .map(|(_, bitfield, ty)| (bitfield.clone(), ty.clone()))
+    }
+
+    // get number of items for the field from the collection
+    fn count_pack(&mut self, ty: &Ident, items: &syn::Member, items_ty: &syn::Type, bitfield: &Bitfield, name: &str, position: &TokenStream) {
+        add_bound(&mut self.generics, items_ty, quote! {
+            #items_ty: bitwrap::BitWrapCount
+        });
+
+        self.pack_list.extend(quote! {
+            let value = bitwrap::BitWrapCount::count(&self.#items);
+        });
+
+        let bits = bitfield.bits;
+        if ! bitfield.truncate && bits != 128 {
+            let error = self.error_at(quote! { InvalidValue }, name, position);
+            self.pack_list.extend(quote! {
+                if (value as u128) >> #bits != 0 {
+                    return Err(#error);
+                }
+            });
+        }
+
+        self.pack_list.extend(quote! {
+            let value = value as #ty;
+        });
+    }
+
+    fn build_bitfield_nested(&mut self, member: &syn::Member, field: &syn::Field, bitfield: &Bitfield) -> syn::Result<()> {
+        let field_ty = &field.ty;
+
+        if let syn::Type::Array(array) = field_ty {
+            // [u8; N]
+            self.assert_align(&bitfield.attr)?;
+
+            let access = self.access(member);
+
+            let len = &array.len;
+            match len {
+                syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(v), .. }) => {
+                    self.offset_bits += v.base10_parse::<usize>()? * 8;
+                }
+                _ => {
+                    self.dynamic = true;
+                    self.packed_len_list.extend(quote! {
+                        + #access.len()
+                    });
+                }
+            }
+
+            let error = self.error_at(quote! {
+                BufferTooShort { needed: next - offset }
+            }, &member_name(member), &self.position());
+
+            self.pack_list.extend(quote! {
+                let next = offset + #access.len();
+                if dst.len() >= next {
+                    dst[offset .. next].clone_from_slice(&#access);
+                    offset = next;
+                } else {
+                    return Err(#error);
+                }
+            });
+
+            let local = field_local(member);
+            self.unpacked.push(member.clone());
+            self.unpack_list.extend(quote! {
+                let #local: #field_ty = {
+                    let mut v: #field_ty = [0; #len];
+                    let next = offset + v.len();
+                    if src.len() >= next {
+                        v.clone_from_slice(&src[offset .. next]);
+                        offset = next;
+                        v
+                    } else {
+                        return Err(#error);
+                    }
+                };
+            });
+
+            self.to_bytes_list.extend(quote! {
+                let next = offset + #access.len();
+                dst[offset .. next].copy_from_slice(&#access);
+                offset = next;
+            });
+
+            self.from_bytes_list.extend(quote! {
+                let #local: #field_ty = {
+                    let mut v: #field_ty = [0; #len];
+                    let next = offset + v.len();
+                    v.copy_from_slice(&src[offset .. next]);
+                    offset = next;
+                    v
+                };
+            });
+        } else {
+            // Any object with BitWrap implementation
+            self.pack_list.extend(quote! {
+                let limit = dst.len();
+            });
+
+            self.unpack_list.extend(quote! {
+                let limit = src.len();
+            });
+
+            self.build_bitfield_array(member, field, bitfield)?;
+        }
+
+        Ok(())
+    }
+
+    // nested object with fixed size at any bit position
+    fn build_bitfield_bits(&mut self, member: &syn::Member, field: &syn::Field, bitfield: &Bitfield) -> syn::Result<()> {
+        self.infallible = false;
+
+        let field_ty = &field.ty;
+        let bits = bitfield.bits;
+        let error = &self.options.error;
+        add_bound(&mut self.generics, field_ty, quote! {
+            #field_ty: bitwrap::BitWrapBits
+        });
+        add_bound(&mut self.generics, field_ty, quote! {
+            #error: From<<#field_ty as bitwrap::BitWrapBits>::Error>
+        });
+
+        if ! is_generic_type(field_ty, &self.generics) {
+            let message = format!("size of the nested field `{}` should be {} bits", member_name(member), bits);
+            self.asserts.extend(quote! {
+                const _: () = assert!(<#field_ty as bitwrap::BitWrapBits>::BITS == #bits, #message);
+            });
+        }
+
+        let name = member_name(member);
+        let position = self.position();
+        let bit = 8 - self.bits;
+        let map_err = self.map_err_at(&name, &position, &quote! {
+            <#field_ty as bitwrap::BitWrapBits>::Error
+        });
+        let next = (bit + bits) / 8;
+
+        let access = self.access(member);
+        self.pack_list.extend(quote! {
+            bitwrap::BitWrapBits::pack_bits(&#access, &mut dst[offset ..], #bit) #map_err ?;
+            offset += #next;
+        });
+
+        let local = field_local(member);
+        self.unpacked.push(member.clone());
+        self.unpack_list.extend(quote! {
+            let #local = <#field_ty as bitwrap::BitWrapBits>::unpack_bits(&src[offset ..], #bit) #map_err ?;
+            offset += #next;
+        });
+
+        self.offset_bits += bits;
+        self.bits = 8 - (8 - self.bits + bits) % 8;
+
+        Ok(())
+    }
+
+    fn build_bitfield(&mut self, member: &syn::Member, field: &syn::Field, bitfield: &Bitfield) -> syn::Result<()> {
+        let field_ty = &field.ty;
+
+        if ! bitfield.limit.is_empty() {
+            let bytes = self.length_into_bytes(&bitfield.limit, bitfield, Some(field_ty), &member_name(member))?;
+
+            self.pack_list.extend(quote! {
+                let limit = offset.saturating_add(#bytes);
+            });
+
+            self.unpack_list.extend(quote! {
+                let limit = offset.saturating_add(#bytes);
+            });
+
+            return self.build_bitfield_array(member, field, bitfield);
+        }
+
+        // nested bitfield (attribute without size)
+        if bitfield.bits == 0 {
+            return self.build_bitfield_nested(member, field, bitfield);
+        }
+
+        if bitfield.nested {
+            return self.build_bitfield_bits(member, field, bitfield);
+        }
+
+        let bits = bitfield.bits;
+
+        // signed integers should fit into the field type
+        match signed_type_bits(field_ty) {
+            Some(ty_bits) if bitfield.name.is_empty() && bits > ty_bits => {
+                return Err(syn::Error::new_spanned(&bitfield.attr, format!(
+                    "bitfield size {} exceeds size of the field type {} bits",
+                    bits,
+                    ty_bits,
+                )));
+            }
+            Some(_) if bitfield.name.is_empty() => {}
+            _ if bitfield.sign.is_some() => {
+                return Err(syn::Error::new_spanned(
+                    &bitfield.attr,
+                    "sign encoding requires field with signed integer type",
+                ));
+            }
+            _ => {}
+        }
+
+        let field_name = &bitfield.name;
+        let field_value = &bitfield.value;
+
+        let name = if field_name.is_empty() {
+            member_name(member)
+        } else {
+            field_name.to_string()
+        };
+
+        // field position before bits are packed
+        let position = self.position();
+        let bits_before = 8 - self.bits;
+
+        // check buffer len. fields in the run are checked on the run begin
+        if self.bits == 8 && self.run_fields == 0 {
+            let bytes = (bits + 7) / 8;
+            let error = self.error_at(quote! {
+                BufferTooShort { needed: #bytes }
+            }, &name, &position);
+
+            self.pack_list.extend(quote! {
+                if #bytes + offset > dst.len() {
+                    return Err(#error);
+                }
+
+                dst[offset] = 0;
+            });
+
+            self.unpack_list.extend(quote! {
+                if #bytes + offset > src.len() {
+                    return Err(#error);
+                }
+            });
+        } else if self.bits == 8 && self.words.is_empty() {
+            self.pack_list.extend(quote! {
+                dst[offset] = 0;
+            });
+        }
+
+        // get type to store bits
+        let ty = bits_type(bits);
+        let endian = bitfield.endian.unwrap_or(self.options.endian);
+
+        if ! field_name.is_empty() {
+            //  name + value
+
+            if let (false, Some((length, length_ty))) = (field_value.is_empty(), self.length_field(field_name)) {
+                // length in bytes is converted into units of the length expression
+                self.infallible = false;
+
+                let convert = self.length_from_bytes(&length, Some(&length_ty), &name)?;
+                let check = if bitfield.truncate || bits == 128 {
+                    quote! {}
+                } else {
+                    let error = self.error_at(quote! { InvalidValue }, &name, &position);
+                    quote! {
+                        if (value as u128) >> #bits != 0 {
+                            return Err(#error);
+                        }
+                    }
+                };
+
+                self.pack_list.extend(quote! {
+                    let value = ( #field_value ) as i128 ;
+                    #convert
+                    #check
+                    let value = value as #ty ;
+                });
+            } else if ! field_value.is_empty() {
+                // to_bytes could not return error for the value out of the field
+                if ! bitfield.truncate && bits != 128 && ! literal_fits(field_value, bits) {
+                    self.infallible = false;
+                }
+
+                self.to_bytes_list.extend(quote! {
+                    let value = ( #field_value ) as #ty ;
+                });
+
+                if bitfield.truncate || bits == 128 {
+                    self.pack_list.extend(quote! {
+                        let value = ( #field_value ) as #ty ;
+                    });
+                } else {
+                    let error = self.error_at(quote! { InvalidValue }, &name, &position);
+                    self.pack_list.extend(quote! {
+                        let value = ( #field_value ) as u128 ;
+                        if value >> #bits != 0 {
+                            return Err(#error);
+                        }
+                        let value = value as #ty ;
+                    });
+                }
+            } else if ! bitfield.length_of.is_empty() {
+                // value is set after covered fields are packed
+                self.infallible = false;
+
+                let start = region_local(field_name, "start");
+                let saved = region_local(field_name, "offset");
+                let (_, patch, _) = self.make_chunks(&ty, bits, endian);
+                let convert = self.length_from_bytes(bitfield, None, &name)?;
+                let check = if bitfield.truncate || bits == 128 {
+                    quote! {}
+                } else {
+                    let error = self.error_at(quote! { InvalidValue }, &name, &self.position());
+                    quote! {
+                        if (value as u128) >> #bits != 0 {
+                            return Err(#error);
+                        }
+                    }
+                };
+
+                self.patches.push((field_name.to_string(), quote! {
+                    {
+                        let value = offset - #start;
+                        let mut offset = #saved;
+                        #convert
+                        #check
+                        let value = value as #ty;
+                        #patch
+                        let _ = offset;
+                    }
+                }));
+
+                self.pack_list.extend(quote! {
+                    let #saved = offset;
+                    let value: #ty = 0;
+                });
+            } else if let Some((body, body_ty)) = self.tag_field(field_name) {
+                self.infallible = false;
+                self.tag_pack(&ty, &body, &body_ty, &name);
+                if ! bitfield.truncate {
+                    self.overflow_check(&ty, bits, &name, &position);
+                }
+            } else if let Some((items, items_ty)) = self.count_field(field_name) {
+                self.infallible = false;
+                self.count_pack(&ty, &items, &items_ty, bitfield, &name, &position);
+            } else {
+                return Err(syn::Error::new_spanned(
+                    field_name,
+                    "value is required for virtual field",
+                ));
+            }
+
+            if bitfield.length_of.is_empty() {
+                let assign = quote! {
+                    #[allow(unused_variables)]
+                    let #field_name = value ;
+                };
+                self.pack_list.extend(assign.clone());
+                self.to_bytes_list.extend(assign);
+            }
+
+            // TODO: skip if name started with _
+
+            self.macro_make_bits(&ty, bits, endian);
+
+            // compare with expected value
+            match &bitfield.check {
+                Some(Check::Strict) => {
+                    self.infallible = false;
+                    let position = unpack_position(&position, bits_before, bits);
+                    let error = self.error_at(quote! { Constraint }, &name, &position);
+                    self.unpack_list.extend(quote! {
+                        if value != ( #field_value ) as #ty {
+                            return Err(#error);
+                        }
+                    });
+                }
+                Some(Check::Lenient(flag)) => {
+                    let local = field_local(flag);
+                    self.unpacked.push(flag.clone());
+                    let unpack = quote! {
+                        let #local = value != ( #field_value ) as #ty ;
+                    };
+                    self.unpack_list.extend(unpack.clone());
+                    self.from_bytes_list.extend(unpack);
+                }
+                None => {}
+            }
+
+            let assign = quote! {
+                #[allow(unused_variables)]
+                let #field_name = value ;
+            };
+            self.unpack_list.extend(assign.clone());
+            self.from_bytes_list.extend(assign);
+
+            return Ok(());
+        }
+
+        let local = field_local(member);
+
+        if ! is_bool_type(field_ty) {
+            add_bound(&mut self.generics, field_ty, quote! {
+                #field_ty: core::convert::TryFrom<#ty> + Copy
+            });
+            add_bound(&mut self.generics, field_ty, quote! {
+                #ty: core::convert::TryFrom<#field_ty>
+            });
+            let error = &self.options.error;
+            add_bound(&mut self.generics, field_ty, quote! {
+                #error:
+                    From<<#field_ty as core::convert::TryFrom<#ty>>::Error> +
+                    From<<#ty as core::convert::TryFrom<#field_ty>>::Error>
+            });
+        }
+
+        // conversion without errors for the fixed-size array.
+        // only if field type has the field size or value is truncated with option
+        let access = self.access(member);
+        let lossless = |field_bits: usize| field_bits == bits || bitfield.truncate;
+        let infallible = match (unsigned_type_bits(field_ty), signed_type_bits(field_ty)) {
+            (Some(field_bits), _) if lossless(field_bits) => Some((
+                quote! { #access as #ty },
+                quote! { value as #field_ty },
+            )),
+            (_, Some(field_bits)) if lossless(field_bits) => Some((
+                convert_into(&ty, field_ty, &access, bitfield, &|_| quote! {}),
+                convert_from(&ty, field_ty, &quote! { value }, bitfield, &|_| quote! {}),
+            )),
+            _ if is_bool_type(field_ty) => Some((
+                convert_into(&ty, field_ty, &access, bitfield, &|_| quote! {}),
+                convert_from(&ty, field_ty, &quote! { value }, bitfield, &|_| quote! {}),
+            )),
+            _ => None,
+        };
+
+        if let Some((value_into, _)) = &infallible {
+            self.to_bytes_list.extend(quote! {
+                let value: #ty = #value_into ;
+            });
+        } else {
+            self.infallible = false;
+        }
+
+        // set default conversion field -> bits
+        if let Some((body, body_ty)) = self.tag_field(&quote! { #member }) {
+            self.infallible = false;
+            self.tag_pack(&ty, &body, &body_ty, &name);
+            add_bound(&mut self.generics, &body_ty, quote! {
+                #body_ty: bitwrap::BitWrapTag<#field_ty>
+            });
+            if ! bitfield.truncate {
+                self.overflow_check(&ty, bits, &name, &position);
+            }
+        } else if let Some((items, items_ty)) = self.count_field(&quote! { #member }) {
+            self.infallible = false;
+            self.count_pack(&ty, &items, &items_ty, bitfield, &name, &position);
+        } else if signed_type_bits(field_ty).is_some() {
+            if ! bitfield.truncate {
+                self.signed_check(member, field_ty, bitfield, &name, &position);
+            }
+            let value = convert_into(&ty, field_ty, &access, bitfield, &|_| quote! {});
+            self.pack_list.extend(quote! {
+                let value: #ty = #value ;
+            });
+        } else {
+            let map_err = |source: &TokenStream| self.map_err_at(&name, &position, source);
+            let value = convert_into(&ty, field_ty, &access, bitfield, &map_err);
+            self.pack_list.extend(quote! {
+                let value: #ty = #value ;
+            });
+            if ! bitfield.truncate && ! is_bool_type(field_ty) {
+                self.overflow_check(&ty, bits, &name, &position);
+            }
+        }
+
+        self.macro_make_bits(&ty, bits, endian);
+
+        let position = unpack_position(&position, bits_before, bits);
+
+        // set default conversion bits -> field
+        let map_err = |source: &TokenStream| self.map_err_at(&name, &position, source);
+        let value = convert_from(&ty, field_ty, &quote! { value }, bitfield, &map_err);
+        self.unpacked.push(member.clone());
+        self.unpack_list.extend(quote! {
+            let #local: #field_ty = #value ;
+        });
+
+        if let Some((_, value_from)) = &infallible {
+            self.from_bytes_list.extend(quote! {
+                let #local: #field_ty = #value_from ;
+            });
+        }
+
+        Ok(())
+    }
+
+    // begin of the fixed-size fields with one buffer check.
+    // aligned run with big-endian fields is packed by words
+    fn run_start(&mut self, run: &[(syn::Member, &syn::Field, Bitfield)]) {
+        let mut pack_checks = TokenStream::new();
+        let mut unpack_checks = TokenStream::new();
+
+        // field position from the run begin
+        let mut byte = 0;
+        let mut bit = 8 - self.bits;
+        let total = (bit + run.iter().map(|v| v.2.bits).sum::<usize>() + 7) / 8;
+        // last checked byte
+        let mut checked = 0;
+        // field end in bits from the run begin, could be in word
+        let mut ends = Vec::new();
+        let mut words = self.options.bit_order == BitOrder::Msb0 && bit == 0;
+
+        for (member, _, bitfield) in run {
+            let bits = bitfield.bits;
+            let name = if bitfield.name.is_empty() {
+                member_name(member)
+            } else {
+                bitfield.name.to_string()
+            };
+
+            let needed = (bit + bits + 7) / 8;
+            let position = match (byte, bit) {
+                (0, 0) => quote! { offset * 8 },
+                (0, b) => quote! { offset * 8 + #b },
+                (k, 0) => quote! { (offset + #k) * 8 },
+                (k, b) => quote! { (offset + #k) * 8 + #b },
+            };
+            let error = self.error_at(quote! {
+                BufferTooShort { needed: #needed }
+            }, &name, &position);
+
+            // first field out of the buffer. fields ended in the checked byte are skipped
+            let end = byte + needed;
+            if end == total && checked < total {
+                pack_checks.extend(quote! { return Err(#error); });
+                unpack_checks.extend(quote! { return Err(#error); });
+            } else if end > checked {
+                pack_checks.extend(quote! {
+                    if offset + #end > dst.len() {
+                        return Err(#error);
+                    }
+                });
+                unpack_checks.extend(quote! {
+                    if offset + #end > src.len() {
+                        return Err(#error);
+                    }
+                });
+            }
+            checked = checked.max(end);
+
+            // little-endian field in word only if it is aligned full-size integer
+            let (_, ty_bits) = signed_bits_type(&bits_type(bits));
+            if bitfield.endian.unwrap_or(self.options.endian) == Endian::Little &&
+                bits != 8 &&
+                (bit != 0 || bits != ty_bits)
+            {
+                words = false;
+            }
+
+            bit += bits;
+            byte += bit / 8;
+            bit %= 8;
+            ends.push(byte * 8 + bit);
+        }
+
+        self.pack_list.extend(quote! {
+            if offset + #total > dst.len() {
+                #pack_checks
+            }
+        });
+
+        self.unpack_list.extend(quote! {
+            if offset + #total > src.len() {
+                #unpack_checks
+            }
+        });
+
+        self.run_fields = run.len();
+        self.words.clear();
+        self.word_bits = 0;
+
+        if ! words {
+            return;
+        }
+
+        // split run into words up to 64 bits, or 128 bits if there is no byte boundary.
+        // word ends at byte boundary or at the run end
+        let mut start = 0;
+        let mut start_bit = 0;
+
+        while start < ends.len() {
+            let mut cut = None;
+
+            for (index, end) in ends.iter().enumerate().skip(start) {
+                let size = end - start_bit;
+                if size > 128 {
+                    break;
+                }
+
+                if end % 8 == 0 || index + 1 == ends.len() {
+                    if size <= 64 {
+                        cut = Some(index + 1);
+                    } else {
+                        if cut.is_none() {
+                            cut = Some(index + 1);
+                        }
+                        break;
+                    }
+                }
+            }
+
+            let cut = match cut {
+                Some(v) => v,
+                None => {
+                    // field could not be packed into word. use bytes for the whole run
+                    self.words.clear();
+                    self.pack_list.extend(quote! {
+                        dst[offset] = 0;
+                    });
+                    return;
+                }
+            };
+
+            let end = ends[cut - 1];
+            self.words.push((cut - start, (end - start_bit + 7) / 8));
+            start = cut;
+            start_bit = end;
         }
     }
 
-    fn assert_align(&self) {
-        assert_eq!(self.bits, 8, "bitwrap not aligned");
+    // multiplier and divisor to convert length in units into bytes
+    fn length_scale(&mut self, bitfield: &Bitfield, field_ty: Option<&syn::Type>) -> syn::Result<(Option<TokenStream>, usize)> {
+        match bitfield.unit.unwrap_or(Unit::Bytes(1)) {
+            Unit::Bytes(1) => Ok((None, 1)),
+            Unit::Bytes(v) => {
+                let v = proc_macro2::Literal::i128_unsuffixed(v as i128);
+                Ok((Some(quote! { #v }), 1))
+            }
+            Unit::Bits => Ok((None, 8)),
+            Unit::Elements => {
+                let item = field_ty.and_then(|v| wrapped_type(v, "Vec")).ok_or_else(|| syn::Error::new_spanned(
+                    &bitfield.attr,
+                    "unit elements requires field with type Vec<T>",
+                ))?;
+                add_bound(&mut self.generics, item, quote! {
+                    #item: bitwrap::BitWrapSized
+                });
+                Ok((Some(quote! { (<#item as bitwrap::BitWrapSized>::SIZE as i128) }), 1))
+            }
+        }
     }
 
-    fn macro_make_bits(&mut self, ty: &Ident, bits: usize) {
-        let mut bits = bits;
+    // convert length in units into bytes
+    fn length_into_bytes(&mut self, len: &TokenStream, bitfield: &Bitfield, field_ty: Option<&syn::Type>, name: &str) -> syn::Result<TokenStream> {
+        if bitfield.unit.is_none() && bitfield.adjust.is_empty() {
+            return Ok(quote! { ( #len ) as usize });
+        }
 
-        self.unpack_list.extend(quote! {
-            let mut value: #ty = 0;
-        });
+        let (mul, div) = self.length_scale(bitfield, field_ty)?;
+        let error = self.error_at(quote! { InvalidValue }, name, &self.position());
 
-        while bits > self.bits {
-            let shift = bits - self.bits; // value left shift
-            let mask = 0xFFu8 >> (8 - self.bits);
+        let mut result = quote! {
+            let v = ( #len ) as i128;
+        };
 
-            self.pack_list.extend(quote! {
-                dst[offset] |= ((value >> #shift) as u8) & #mask;
-                offset += 1;
-                dst[offset] = 0;
+        if let Some(mul) = mul {
+            result.extend(quote! {
+                let v = match v.checked_mul(#mul) {
+                    Some(v) => v,
+                    None => return Err(#error),
+                };
             });
+        }
 
-            self.unpack_list.extend(quote! {
-                value |= ((src[offset] & #mask) as #ty) << #shift;
-                offset += 1;
+        if div != 1 {
+            let div = proc_macro2::Literal::usize_unsuffixed(div);
+            result.extend(quote! {
+                if v % #div != 0 {
+                    return Err(#error);
+                }
+                let v = v / #div;
             });
+        }
 
-            bits -= self.bits;
-            self.bits = 8;
+        let adjust = &bitfield.adjust;
+        if ! adjust.is_empty() {
+            result.extend(quote! {
+                let v = match v.checked_add( #adjust ) {
+                    Some(v) => v,
+                    None => return Err(#error),
+                };
+            });
         }
 
-        self.bits -= bits;
+        // negative length or length of the u128 field out of the i128 range
+        Ok(quote! {{
+            #result
+            match <usize as core::convert::TryFrom<i128>>::try_from(v) {
+                Ok(v) => v,
+                Err(_) => return Err(#error),
+            }
+        }})
+    }
+
+    // convert number of bytes in the `value` into length in units
+    fn length_from_bytes(&mut self, bitfield: &Bitfield, field_ty: Option<&syn::Type>, name: &str) -> syn::Result<TokenStream> {
+        if bitfield.unit.is_none() && bitfield.adjust.is_empty() {
+            return Ok(quote! {});
+        }
 
-        let shift = self.bits; // byte right shift
-        let mask = 0xFFu8 >> (8 - bits);
+        let (mul, div) = self.length_scale(bitfield, field_ty)?;
+        let error = self.error_at(quote! { InvalidValue }, name, &self.position());
 
-        if shift == 0 {
-            self.pack_list.extend(quote! {
-                dst[offset] |= (value as u8) & #mask;
-                offset += 1;
-            });
+        let mut result = quote! {
+            let v = value as i128;
+        };
 
-            self.unpack_list.extend(quote! {
-                value |= (src[offset] & #mask) as #ty;
-                offset += 1;
+        let adjust = &bitfield.adjust;
+        if ! adjust.is_empty() {
+            result.extend(quote! {
+                let v = v - ( #adjust );
+                if v < 0 {
+                    return Err(#error);
+                }
             });
+        }
 
-            self.bits = 8;
-        } else {
-            self.pack_list.extend(quote! {
-                dst[offset] |= ((value as u8) & #mask) << #shift;
+        if div != 1 {
+            let div = proc_macro2::Literal::usize_unsuffixed(div);
+            result.extend(quote! {
+                let v = v * #div;
             });
+        }
 
-            self.unpack_list.extend(quote! {
-                value |= ((src[offset] >> #shift) & #mask) as #ty;
+        if let Some(mul) = mul {
+            result.extend(quote! {
+                if v % #mul != 0 {
+                    return Err(#error);
+                }
+                let v = v / #mul;
             });
         }
+
+        Ok(quote! {
+            let value = {
+                #result
+                v
+            };
+        })
     }
 
-    fn build_bitfield_array(&mut self, field: &syn::Field) {
-        self.assert_align();
+    // begin of the fields covered by the length field.
+    // unpack limits source buffer to the length
+    fn region_start(&mut self, length: &Bitfield, member: &syn::Member, bitfield: &Bitfield) -> syn::Result<()> {
+        self.assert_align(&bitfield.attr)?;
+
+        let name = &length.name;
+        let start = region_local(name, "start");
+        let limit = region_local(name, "limit");
+        let saved = region_local(name, "src");
 
-        let field_ident = &field.ident;
+        let error = self.error_at(quote! {
+            BufferTooShort { needed: #limit - offset }
+        }, &member_name(member), &self.position());
 
         self.pack_list.extend(quote! {
-            if dst.len() >= limit {
-                offset += self.#field_ident.pack(&mut dst[offset .. limit])?;
-            } else {
-                return Err(bitwrap::BitWrapError);
+            let #start = offset;
+        });
+
+        let bytes = self.length_into_bytes(name, length, None, &name.to_string())?;
+
+        self.unpack_list.extend(quote! {
+            let #limit = offset.saturating_add(#bytes);
+            if #limit > src.len() {
+                return Err(#error);
             }
+            let #saved = src;
+            let src = &src[.. #limit];
         });
 
+        Ok(())
+    }
+
+    // end of the fields covered by the length field.
+    // pack sets the length field, unpack restores source buffer
+    fn region_end(&mut self, length: &Bitfield) -> syn::Result<()> {
+        if self.bits != 8 {
+            return Err(syn::Error::new_spanned(&length.attr, format!(
+                "length_of fields should end at byte boundary, current bit offset is {}",
+                self.bit_offset(),
+            )));
+        }
+
+        let name = &length.name;
+        let limit = region_local(name, "limit");
+        let saved = region_local(name, "src");
+
+        let error = self.error_at(quote! { TrailingData }, &name.to_string(), &self.position());
+
+        let key = name.to_string();
+        if let Some((_, patch)) = self.patches.iter().find(|(v, _)| *v == key) {
+            self.pack_list.extend(patch.clone());
+        }
+
         self.unpack_list.extend(quote! {
-            if src.len() >= limit {
-                offset += self.#field_ident.unpack(&src[offset .. limit])?;
-            } else {
-                return Err(bitwrap::BitWrapError);
+            if offset != #limit {
+                return Err(#error);
             }
+            let src = #saved;
         });
+
+        Ok(())
     }
 
-    fn build_bitfield_nested(&mut self, field: &syn::Field) {
-        let field_ty = &field.ty;
-        let field_ident = &field.ident;
+    // bind values of the previous fields used in the condition.
+    // returns bindings for pack and for unpack
+    fn condition_bindings(&self, condition: &TokenStream) -> (TokenStream, TokenStream) {
+        fn collect(stream: TokenStream, list: &mut Vec<Ident>) {
+            for item in stream {
+                match item {
+                    TokenTree::Ident(v) => list.push(v),
+                    TokenTree::Group(v) => collect(v.stream(), list),
+                    _ => {}
+                }
+            }
+        }
 
-        if let syn::Type::Array(_) = field_ty {
-            // [u8; N]
+        let mut idents = Vec::new();
+        collect(condition.clone(), &mut idents);
+
+        let mut pack = TokenStream::new();
+        let mut unpack = TokenStream::new();
+        let mut done: Vec<String> = Vec::new();
+
+        for ident in idents {
+            let name = ident.to_string();
+            if done.contains(&name) || ! self.unpacked.iter().any(|v| member_name(v) == name) {
+                continue;
+            }
+
+            let member = syn::Member::Named(ident.clone());
+            let local = field_local(&member);
+            pack.extend(quote! { let #ident = self.#member; });
+            unpack.extend(quote! { let #ident = #local; });
+            done.push(name);
+        }
+
+        (pack, unpack)
+    }
+
+    // begin of the fields with condition.
+    // code of the group is collected separately and wrapped on the group end
+    fn condition_start(&mut self, key: String, bitfield: &Bitfield) -> Condition {
+        let condition = &bitfield.condition;
+        let (pack_bind, unpack_bind) = self.condition_bindings(condition);
+        let (pack_present, unpack_present) = if pack_bind.is_empty() {
+            (quote! { #condition }, quote! { #condition })
+        } else {
+            (quote! {{ #pack_bind #condition }}, quote! {{ #unpack_bind #condition }})
+        };
+
+        self.dynamic = true;
+        self.infallible = false;
+
+        Condition {
+            key,
+            attr: bitfield.attr.clone(),
+            pack_present,
+            unpack_present,
+            position: self.position(),
+            offset_bits: self.offset_bits,
+            pack_list: std::mem::take(&mut self.pack_list),
+            unpack_list: std::mem::take(&mut self.unpack_list),
+            packed_len_list: std::mem::take(&mut self.packed_len_list),
+            members: Vec::new(),
+        }
+    }
+
+    // field packed only if condition is true
+    fn build_conditional(&mut self, member: &syn::Member, field: &syn::Field, bitfield: &Bitfield, group: &mut Condition) -> syn::Result<()> {
+        if ! bitfield.name.is_empty() {
+            return self.build_bitfield(member, field, bitfield);
+        }
+
+        let inner_ty = wrapped_type(&field.ty, "Option").ok_or_else(|| syn::Error::new_spanned(
+            &bitfield.attr,
+            "field with condition should have type Option<T>",
+        ))?;
+
+        let mut inner_field = field.clone();
+        inner_field.ty = inner_ty.clone();
+
+        let value = region_local(&quote! { option }, &member_name(member));
+        self.access = Some(quote! { (*#value) });
+        let result = self.build_bitfield(member, &inner_field, bitfield);
+        self.access = None;
+
+        group.members.push(member.clone());
+        result
+    }
+
+    // end of the fields with condition
+    fn condition_end(&mut self, group: Condition) -> syn::Result<()> {
+        let bits = self.offset_bits - group.offset_bits;
+        if bits % 8 != 0 {
+            return Err(syn::Error::new_spanned(&group.attr, format!(
+                "fields with condition should have size multiple of 8 bits, total size is {} bits",
+                bits,
+            )));
+        }
+
+        if group.members.is_empty() {
+            return Err(syn::Error::new_spanned(
+                &group.attr,
+                "fields with condition should include field with type Option<T>",
+            ));
+        }
+
+        // fixed-size fields are counted only if present
+        self.offset_bits = group.offset_bits;
+
+        let inner_pack = std::mem::replace(&mut self.pack_list, group.pack_list);
+        let inner_unpack = std::mem::replace(&mut self.unpack_list, group.unpack_list);
+        let inner_len = std::mem::replace(&mut self.packed_len_list, group.packed_len_list);
+
+        let present = Ident::new("present", Span::mixed_site());
+        let pack_present = &group.pack_present;
+        let unpack_present = &group.unpack_present;
+
+        // Option fields, their values on pack and unpacked values
+        let mut fields = Vec::new();
+        let mut values = Vec::new();
+        let mut locals = Vec::new();
+
+        self.pack_list.extend(quote! {
+            let #present = #pack_present;
+        });
+
+        for member in &group.members {
+            let value = region_local(&quote! { option }, &member_name(member));
+            let local = field_local(member);
+            fields.push(quote! { &self.#member });
+            values.push(quote! { Some(#value) });
+            locals.push(local);
+
+            let error = self.error_at(quote! { Constraint }, &member_name(member), &group.position);
             self.pack_list.extend(quote! {
-                let next = offset + self.#field_ident.len();
-                if dst.len() >= next {
-                    dst[offset .. next].clone_from_slice(&self.#field_ident);
-                    offset = next;
-                } else {
-                    return Err(bitwrap::BitWrapError);
+                if #present != self.#member.is_some() {
+                    return Err(#error);
+                }
+            });
+        }
+
+        let bytes = bits / 8;
+
+        if let ([field], [value], [local]) = (fields.as_slice(), values.as_slice(), locals.as_slice()) {
+            self.pack_list.extend(quote! {
+                if let #value = #field {
+                    #inner_pack
                 }
             });
 
             self.unpack_list.extend(quote! {
-                let next = offset + self.#field_ident.len();
-                if src.len() >= next {
-                    self.#field_ident.clone_from_slice(&src[offset .. next]);
-                    offset = next;
+                let #present = #unpack_present;
+                let #local = if #present {
+                    #inner_unpack
+                    Some(#local)
                 } else {
-                    return Err(bitwrap::BitWrapError);
+                    None
+                };
+            });
+
+            self.packed_len_list.extend(quote! {
+                + match #field {
+                    #value => #bytes #inner_len,
+                    None => 0,
                 }
             });
         } else {
-            // Any object with BitWrap implementation
             self.pack_list.extend(quote! {
-                let limit = dst.len();
+                if let ( #(#values),* ) = ( #(#fields),* ) {
+                    #inner_pack
+                }
             });
 
+            let none = locals.iter().map(|_| quote! { None });
             self.unpack_list.extend(quote! {
-                let limit = src.len();
+                let #present = #unpack_present;
+                let ( #(#locals),* ) = if #present {
+                    #inner_unpack
+                    ( #(Some(#locals)),* )
+                } else {
+                    ( #(#none),* )
+                };
             });
 
-            self.build_bitfield_array(field);
+            self.packed_len_list.extend(quote! {
+                + match ( #(#fields),* ) {
+                    ( #(#values),* ) => #bytes #inner_len,
+                    _ => 0,
+                }
+            });
         }
-    }
 
-    fn build_bitfield(&mut self, field: &syn::Field, tokens: &TokenStream) {
-        // nested bitfield (attribute without arguments)
-        if tokens.is_empty() {
-            self.build_bitfield_nested(field);
-            return;
-        }
+        Ok(())
+    }
 
+    // newtype with single bitfield converts from and into bits type
+    fn build_newtype(&self, member: &syn::Member, field: &syn::Field, bitfield: &Bitfield) -> TokenStream {
+        let struct_id = &self.struct_id;
         let field_ty = &field.ty;
-        let field_ident = &field.ident;
-
-        let tokens = tokens.clone();
-        let tree = tokens.into_iter().next().unwrap();
-        let group = match tree {
-            TokenTree::Group(v) => v.stream(),
-            _ => unreachable!(),
+        let ty = bits_type(bitfield.bits);
+
+        let value_into = convert_into(&ty, field_ty, &quote! { value.#member }, bitfield, &|_| quote! {});
+        let value_from = convert_from(&ty, field_ty, &quote! { value }, bitfield, &|_| quote! {});
+
+        let error = &self.options.error;
+        let (impl_generics, ty_generics, where_clause) = self.generics.split_for_impl();
+
+        // signed value should fit into the field size
+        let check = match signed_overflow(field_ty, bitfield) {
+            Some(condition) if ! bitfield.truncate => quote! {
+                let v: #field_ty = value.#member;
+                if #condition {
+                    return Err(<#error as From<bitwrap::BitWrapError>>::from(
+                        bitwrap::BitWrapError::new(bitwrap::BitWrapErrorKind::InvalidValue)
+                    ));
+                }
+            },
+            _ => quote! {},
         };
-        let mut iter = group.into_iter();
 
-        // check first_token
-        let first_token = iter.next().unwrap();
-        let bits = match first_token {
-            TokenTree::Literal(v) => {
-                literal_to_usize(&v).unwrap_or(0)
-            }
-            TokenTree::Ident(v) => {
-                self.pack_list.extend(quote! {
-                    let limit = offset + ( #v ) as usize;
-                });
+        quote! {
+            impl #impl_generics core::convert::TryFrom<#ty> for #struct_id #ty_generics #where_clause {
+                type Error = #error;
 
-                self.unpack_list.extend(quote! {
-                    let limit = offset + ( #v ) as usize;
-                });
+                fn try_from(value: #ty) -> Result<Self, Self::Error> {
+                    Ok(Self(#value_from))
+                }
+            }
 
-                self.build_bitfield_array(field);
+            impl #impl_generics core::convert::TryFrom<#struct_id #ty_generics> for #ty #where_clause {
+                type Error = #error;
 
-                return;
+                fn try_from(value: #struct_id #ty_generics) -> Result<Self, Self::Error> {
+                    #check
+                    Ok(#value_into)
+                }
             }
-            _ => panic!("bitfield argument #1 has wrong type")
-        };
-
-        if bits == 0 || bits > 128 {
-            panic!("bitfield argument #1 should be a number in range 1 ..= 128");
         }
+    }
 
-        let mut field_name = TokenStream::new();
-        let mut field_value = TokenStream::new();
+    fn build(&mut self, data: &syn::DataStruct) -> syn::Result<TokenStream> {
+        self.bits = 8;
 
-        // check buffer len
-        if self.bits == 8 {
-            let bytes = (bits + 7) / 8;
+        let mut bitfields = Vec::new();
 
-            self.pack_list.extend(quote! {
-                if #bytes + offset > dst.len() {
-                    return Err(bitwrap::BitWrapError);
+        for (index, field) in data.fields.iter().enumerate() {
+            let member = match &field.ident {
+                Some(ident) => {
+                    self.fields.push(ident.clone());
+                    syn::Member::Named(ident.clone())
                 }
+                None => syn::Member::Unnamed(syn::Index::from(index)),
+            };
 
-                dst[offset] = 0;
-            });
+            for attr in field.attrs.iter().filter(|v| v.path.is_ident("bitfield")) {
+                let bitfield = Bitfield::parse(attr)?;
 
-            self.unpack_list.extend(quote! {
-                if #bytes + offset > src.len() {
-                    return Err(bitwrap::BitWrapError);
+                if let Some(tag) = &bitfield.tag {
+                    self.tags.push((tag.to_string(), member.clone(), field.ty.clone()));
                 }
-            });
-        }
 
-        // get type to store bits
-        let ty = bits_type(bits);
+                if let Some(count) = &bitfield.count {
+                    self.counts.push((count.to_string(), member.clone(), field.ty.clone()));
+                }
 
-        // parse attributes
-        while let Some(item) = iter.next() {
-            match item {
-                TokenTree::Punct(v) if v.as_char() == ',' => continue,
-                TokenTree::Ident(v) => {
-                    // skip '=' token after ident in attribute options
-                    match iter.next() {
-                        Some(TokenTree::Punct(v)) if v.as_char() == '=' => {}
-                        _ => panic!("unexpected token")
-                    }
+                bitfields.push((member.clone(), field, bitfield));
+            }
+        }
 
-                    match v.to_string().as_str() {
-                        "name" => {
-                            extend_token_stream(&mut field_name, &mut iter);
-                        }
-                        "value" => {
-                            extend_token_stream(&mut field_value, &mut iter);
-                        }
+        // value of the virtual field is in bytes and converted into units on pack.
+        // other length expressions could not be converted
+        for (_, field, bitfield) in bitfields.iter() {
+            if bitfield.limit.is_empty() || (bitfield.unit.is_none() && bitfield.adjust.is_empty()) {
+                continue;
+            }
 
-                        v => panic!("bitfield has unexpected argument: {}", v),
-                    }
-                }
-                _ => panic!("bitfield has wrong format"),
+            let name = bitfield.limit.to_string();
+            if ! bitfields.iter().any(|v| v.2.name.to_string() == name && ! v.2.value.is_empty()) {
+                return Err(syn::Error::new_spanned(
+                    &bitfield.attr,
+                    "unit and adjust require length expression with name of the virtual field with value, use length_of for other fields",
+                ));
             }
+
+            self.lengths.push((name, bitfield.clone(), field.ty.clone()));
         }
 
-        if ! field_name.is_empty() {
-            //  name + value
+        // fields covered by the length fields: (first, last, length)
+        let mut regions = Vec::new();
+
+        // fields from `first` to `last` are in the same group with condition
+        let same_group = |first: usize, last: usize| {
+            let key = bitfields[first].2.condition.to_string();
+            ! key.is_empty() && bitfields[first ..= last].iter().all(|v| v.2.condition.to_string() == key)
+        };
+
+        // virtual fields are declared with member of the next field
+        let is_field = |index: usize, name: &str| {
+            bitfields[index].2.name.is_empty() && member_name(&bitfields[index].0) == name
+        };
 
-            if field_value.is_empty() {
-                panic!("value is required for named filed");
+        for (index, (_, _, bitfield)) in bitfields.iter().enumerate() {
+            let (first, last) = match (bitfield.length_of.first(), bitfield.length_of.last()) {
+                (Some(first), Some(last)) => (member_name(first), member_name(last)),
+                _ => continue,
+            };
+
+            let start = (index + 1 .. bitfields.len())
+                .find(|v| is_field(*v, &first))
+                .ok_or_else(|| syn::Error::new_spanned(
+                    &bitfield.length_of[0],
+                    "length_of field should be a bitfield declared after the length field",
+                ))?;
+
+            let end = (start .. bitfields.len())
+                .rev()
+                .find(|v| is_field(*v, &last))
+                .ok_or_else(|| syn::Error::new_spanned(
+                    &bitfield.length_of[bitfield.length_of.len() - 1],
+                    "length_of field should be a bitfield declared after the length field",
+                ))?;
+
+            // code inside the group is packed only if condition is true,
+            // so region that begins in the group should end in the same group
+            if ! bitfields[start].2.condition.is_empty() && ! same_group(start, end) {
+                return Err(syn::Error::new_spanned(
+                    &bitfield.attr,
+                    "length_of fields that begin in the fields with condition should end in the same fields",
+                ));
             }
 
-            self.pack_list.extend(quote! {
-                let value = ( #field_value ) as #ty ;
-                let #field_name = value ;
-            });
+            regions.push((start, end, index));
+        }
 
-            // TODO: skip if name started with _
+        // region is limited by the slice of the outer region,
+        // so outer region starts first and regions could not cross each other
+        regions.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+        for (i, (start, end, length)) in regions.iter().enumerate() {
+            if regions[.. i].iter().any(|v| *start <= v.1 && *end > v.1) {
+                return Err(syn::Error::new_spanned(
+                    &bitfields[*length].2.attr,
+                    "length_of fields should not partially overlap length_of fields of another length",
+                ));
+            }
+        }
+
+        // fixed-size fields with one buffer check: (first, end)
+        let simple = |bitfield: &Bitfield| {
+            bitfield.bits != 0 &&
+                bitfield.limit.is_empty() &&
+                ! bitfield.nested
+        };
+        // buffer is limited on the region begin and restored after the region end.
+        // fields with condition are checked only if present
+        let boundary = |index: usize| {
+            regions.iter().any(|v| v.0 == index || v.1 + 1 == index) ||
+                bitfields[index].2.condition.to_string() != bitfields[index - 1].2.condition.to_string()
+        };
 
-            self.macro_make_bits(&ty, bits);
+        let mut runs = Vec::new();
+        let mut index = 0;
 
-            self.unpack_list.extend(quote! {
-                let #field_name = value ;
-            });
+        while index < bitfields.len() {
+            if ! simple(&bitfields[index].2) {
+                index += 1;
+                continue;
+            }
 
-            return;
+            let mut end = index + 1;
+            while end < bitfields.len() && simple(&bitfields[end].2) && ! boundary(end) {
+                end += 1;
+            }
+
+            runs.push((index, end));
+            index = end;
         }
 
-        // set default conversion field -> bits
-        match field_ty {
-            syn::Type::Path(v) if v.path.is_ident("bool") => {
-                self.pack_list.extend(quote! {
-                    let value: #ty = if self.#field_ident { 1 } else { 0 } ;
-                });
+        // current group of the fields with condition
+        let mut condition: Option<Condition> = None;
+        // regions that end in the group with condition are closed after the group
+        let mut deferred: Vec<usize> = Vec::new();
+
+        for (index, (member, field, bitfield)) in bitfields.iter().enumerate() {
+            let key = bitfield.condition.to_string();
+            if let Some(group) = condition.take() {
+                if group.key == key {
+                    condition = Some(group);
+                } else {
+                    self.condition_end(group)?;
+                    for length in deferred.drain(..) {
+                        self.region_end(&bitfields[length].2)?;
+                    }
+                }
             }
-            _ => {
-                self.pack_list.extend(quote! {
-                    let value: #ty = #ty::try_from(self.#field_ident)? ;
-                });
+
+            if ! bitfield.condition.is_empty() && condition.is_none() {
+                condition = Some(self.condition_start(key, bitfield));
             }
-        }
 
-        self.macro_make_bits(&ty, bits);
+            for (_, _, length) in regions.iter().filter(|v| v.0 == index) {
+                self.region_start(&bitfields[*length].2, member, bitfield)?;
+            }
 
-        // set default conversion bits -> field
-        match field_ty {
-            syn::Type::Path(v) if v.path.is_ident("bool") => {
-                self.unpack_list.extend(quote! {
-                    self.#field_ident = value != 0 ;
-                });
+            if let Some((_, end)) = runs.iter().find(|v| v.0 == index) {
+                self.run_start(&bitfields[index .. *end]);
             }
-            _ => {
-                self.unpack_list.extend(quote! {
-                    self.#field_ident = #field_ty::try_from(value)? ;
-                });
+
+            if let Some(group) = &mut condition {
+                self.build_conditional(member, field, bitfield, group)?;
+            } else {
+                self.build_bitfield(member, field, bitfield)?;
+            }
+
+            for (start, _, length) in regions.iter().rev().filter(|v| v.1 == index) {
+                if bitfield.condition.is_empty() || same_group(*start, index) {
+                    self.region_end(&bitfields[*length].2)?;
+                } else {
+                    deferred.push(*length);
+                }
             }
         }
-    }
 
-    fn build_field(&mut self, field: &syn::Field) {
-        let bf = Ident::new("bitfield", proc_macro2::Span::call_site());
-        for attr in field.attrs.iter().filter(|v| v.path.segments.len() == 1) {
-            if attr.path.segments[0].ident == bf {
-                self.build_bitfield(field, &attr.tokens);
+        if let Some(group) = condition {
+            self.condition_end(group)?;
+            for length in deferred.drain(..) {
+                self.region_end(&bitfields[length].2)?;
             }
         }
-    }
 
-    fn build(&mut self, data: &syn::DataStruct) -> TokenStream {
-        self.bits = 8;
+        let mut result = TokenStream::new();
 
-        let fields = match &data.fields {
-            syn::Fields::Named(v) => &v.named,
-            syn::Fields::Unnamed(_v) => unimplemented!(),
-            syn::Fields::Unit => unimplemented!(),
-        };
+        let mut newtype = false;
+        if let (syn::Fields::Unnamed(_), [(member, field, bitfield)]) = (&data.fields, bitfields.as_slice()) {
+            if bitfield.bits != 0 && bitfield.name.is_empty() && ! bitfield.nested {
+                result.extend(self.build_newtype(member, field, bitfield));
+                newtype = true;
+            }
+        }
+
+        // fixed-size struct without variable-length fields
+        let sized = ! self.dynamic;
+
+        // struct with option `bits` and newtype could have any number of bits.
+        // it could be nested into another struct but could not be packed alone
+        if let Some(bits) = &self.options.bits {
+            if ! sized {
+                return Err(syn::Error::new_spanned(
+                    bits,
+                    "bits requires struct without variable-length fields",
+                ));
+            }
+        } else if self.bits != 8 && ! newtype {
+            return Err(syn::Error::new_spanned(&self.struct_id, format!(
+                "struct size should be a multiple of 8 bits, total size is {} bits",
+                self.bit_offset(),
+            )));
+        }
+
+        if let Some(size) = &self.options.size {
+            if ! sized {
+                return Err(syn::Error::new_spanned(
+                    size,
+                    "size requires struct without variable-length fields",
+                ));
+            }
 
-        for field in fields {
-            self.build_field(field);
+            let expected = size.base10_parse::<usize>()?;
+            if expected * 8 != self.offset_bits {
+                return Err(syn::Error::new_spanned(size, format!(
+                    "struct size is {} bytes, expected {}",
+                    self.offset_bits / 8,
+                    expected,
+                )));
+            }
         }
 
-        self.assert_align();
+        // build struct from unpacked values, other fields are default.
+        // unpack into existing struct keeps other fields
+        let mut build_list = TokenStream::new();
+        let mut assign_list = TokenStream::new();
+
+        for (index, field) in data.fields.iter().enumerate() {
+            let member = match &field.ident {
+                Some(ident) => syn::Member::Named(ident.clone()),
+                None => syn::Member::Unnamed(syn::Index::from(index)),
+            };
+
+            if self.unpacked.iter().any(|v| member_name(v) == member_name(&member)) {
+                let local = field_local(&member);
+                build_list.extend(quote! { #member: #local, });
+                assign_list.extend(quote! { self.#member = #local; });
+            } else {
+                let field_ty = &field.ty;
+                add_bound(&mut self.generics, field_ty, quote! {
+                    #field_ty: Default
+                });
+                // error for the type without Default points to the field
+                let default = quote_spanned! { field_ty.span() =>
+                    <#field_ty as Default>::default()
+                };
+                build_list.extend(quote! { #member: #default, });
+            }
+        }
 
         let struct_id = &self.struct_id;
+        let error = &self.options.error;
         let pack_list = &self.pack_list;
         let unpack_list = &self.unpack_list;
+        let fixed_len = self.offset_bits / 8;
+        let packed_len_list = &self.packed_len_list;
+        let (impl_generics, ty_generics, where_clause) = self.generics.split_for_impl();
 
-        quote! {
-            impl bitwrap::BitWrapExt for #struct_id {
-                fn pack(&self, dst: &mut [u8]) -> Result<usize, bitwrap::BitWrapError> {
-                    use core::convert::TryFrom as _;
+        result.extend(self.asserts.clone());
+
+        if sized {
+            // pack into the temporary buffer and copy bits to the required position
+            let bits = self.offset_bits;
+            let bytes = (bits + 7) / 8;
+            // bytes to the end of the struct at the bit position
+            let needed = if bits == 0 {
+                quote! { bit / 8 }
+            } else {
+                let round = bits + 7;
+                quote! { (bit + #round) / 8 }
+            };
+            let new = match self.options.bit_order {
+                BitOrder::Msb0 => quote! { new },
+                BitOrder::Lsb0 => quote! { new_lsb0 },
+            };
+            result.extend(quote! {
+                impl #impl_generics bitwrap::BitWrapBits for #struct_id #ty_generics #where_clause {
+                    type Error = #error;
+
+                    const BITS: usize = #bits;
+
+                    #[allow(unused_assignments, unused_variables)]
+                    fn pack_bits(&self, dst: &mut [u8], bit: usize) -> Result<(), Self::Error> {
+                        let needed = #needed;
+                        if dst.len() < needed {
+                            return Err(<#error as From<bitwrap::BitWrapError>>::from(
+                                bitwrap::BitWrapErrorKind::BufferTooShort { needed }.into()
+                            ));
+                        }
+
+                        let mut buffer = [0u8; #bytes];
+                        {
+                            let dst = &mut buffer[..];
+                            let mut offset: usize = 0;
+                            #pack_list
+                        }
+
+                        let mut reader = bitwrap::BitReader::#new(&buffer);
+                        let mut writer = bitwrap::BitWriter::#new(dst);
+                        writer.skip(bit)?;
+                        writer.copy_from(&mut reader, #bits)?;
+                        Ok(())
+                    }
+
+                    #[allow(unused_assignments, unused_variables)]
+                    fn unpack_bits(src: &[u8], bit: usize) -> Result<Self, Self::Error> {
+                        let needed = #needed;
+                        if src.len() < needed {
+                            return Err(<#error as From<bitwrap::BitWrapError>>::from(
+                                bitwrap::BitWrapErrorKind::BufferTooShort { needed }.into()
+                            ));
+                        }
+
+                        let mut buffer = [0u8; #bytes];
+                        let mut reader = bitwrap::BitReader::#new(src);
+                        let mut writer = bitwrap::BitWriter::#new(&mut buffer);
+                        reader.skip(bit)?;
+                        writer.copy_from(&mut reader, #bits)?;
+
+                        let src = &buffer[..];
+                        let mut offset: usize = 0;
+                        #unpack_list
+                        Ok(Self { #build_list })
+                    }
+                }
+            });
+        }
+
+        if self.bits != 8 {
+            return Ok(result);
+        }
+
+        result.extend(quote! {
+            impl #impl_generics bitwrap::BitWrapExt for #struct_id #ty_generics #where_clause {
+                type Error = #error;
+
+                fn pack(&self, dst: &mut [u8]) -> Result<usize, Self::Error> {
                     let mut offset: usize = 0;
                     #pack_list
                     Ok(offset)
                 }
 
-                fn unpack(&mut self, src: &[u8]) -> Result<usize, bitwrap::BitWrapError> {
-                    use core::convert::TryFrom as _;
+                fn packed_len(&self) -> usize {
+                    #fixed_len #packed_len_list
+                }
+
+                fn unpack(&mut self, src: &[u8]) -> Result<usize, Self::Error> {
                     let mut offset: usize = 0;
                     #unpack_list
+                    #assign_list
                     Ok(offset)
                 }
+
+                fn unpack_from(src: &[u8]) -> Result<(Self, usize), Self::Error> {
+                    let mut offset: usize = 0;
+                    #unpack_list
+                    Ok((Self { #build_list }, offset))
+                }
             }
+        });
+
+        if sized {
+            result.extend(quote! {
+                impl #impl_generics bitwrap::BitWrapSized for #struct_id #ty_generics #where_clause {}
+            });
+        }
+
+        if sized && self.infallible {
+            let size = self.offset_bits / 8;
+            let to_bytes_list = &self.to_bytes_list;
+            let from_bytes_list = &self.from_bytes_list;
+            result.extend(quote! {
+                impl #impl_generics #struct_id #ty_generics #where_clause {
+                    /// Packs struct into the fixed-size array
+                    #[allow(unused_assignments)]
+                    pub fn to_bytes(&self) -> [u8; #size] {
+                        let mut dst = [0u8; #size];
+                        let mut offset: usize = 0;
+                        #to_bytes_list
+                        dst
+                    }
+
+                    /// Unpacks struct from the fixed-size array
+                    #[allow(unused_assignments)]
+                    pub fn from_bytes(src: &[u8; #size]) -> Self {
+                        let mut offset: usize = 0;
+                        #from_bytes_list
+                        Self { #build_list }
+                    }
+                }
+            });
         }
+
+        Ok(result)
     }
 }
 
 
-#[proc_macro_derive(BitWrap, attributes(bitfield))]
+#[proc_macro_derive(BitWrap, attributes(bitfield, bitwrap))]
 pub fn bitwrap_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as syn::DeriveInput);
 
-    if let syn::Data::Struct(ref s) = input.data {
-        let mut bitwrap = BitWrapMacro::new(&input.ident);
-        bitwrap.build(s).into()
-    } else {
-        panic!("struct required")
-    }
+    let result = match &input.data {
+        syn::Data::Struct(s) => {
+            BitWrapMacro::new(&input.ident, &input.generics, &input.attrs)
+                .and_then(|mut bitwrap| bitwrap.build(s))
+        }
+        syn::Data::Enum(e) => {
+            BitWrapEnum::new(&input.ident, &input.generics, &input.attrs, e)
+                .map(|bitwrap| bitwrap.build())
+        }
+        syn::Data::Union(u) => Err(syn::Error::new_spanned(
+            u.union_token,
+            "bitwrap could be derived only for struct or enum",
+        )),
+    };
+
+    result.unwrap_or_else(|e| e.to_compile_error()).into()
 }
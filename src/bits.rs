@@ -0,0 +1,326 @@
+use crate::{
+    BitWrapBits,
+    BitWrapError,
+    BitWrapErrorExt,
+    BitWrapErrorKind,
+    BitWrapExt,
+};
+
+
+// mask for the lower bits of the byte
+#[inline]
+fn mask(bits: usize) -> u8 {
+    0xFF >> (8 - bits)
+}
+
+
+/// Reads bits from the byte array.
+///
+/// Bits are numbered from the most significant bit of the byte and
+/// value is read starting from its most significant bit.
+/// With `lsb0` order bits are numbered from the least significant bit
+/// and value is read starting from its least significant bit
+#[derive(Debug, Clone)]
+pub struct BitReader<'a> {
+    src: &'a [u8],
+    position: usize,
+    lsb0: bool,
+}
+
+
+impl<'a> BitReader<'a> {
+    /// Creates reader with `msb0` bit order
+    pub fn new(src: &'a [u8]) -> Self {
+        Self {
+            src,
+            position: 0,
+            lsb0: false,
+        }
+    }
+
+    /// Creates reader with `lsb0` bit order
+    pub fn new_lsb0(src: &'a [u8]) -> Self {
+        Self {
+            src,
+            position: 0,
+            lsb0: true,
+        }
+    }
+
+    /// Returns number of bits read from the begin of the array
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns number of bits left in the array
+    #[inline]
+    pub fn remaining_bits(&self) -> usize {
+        self.src.len() * 8 - self.position
+    }
+
+    /// Returns bytes left in the array starting from the current byte
+    #[inline]
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.src[self.position / 8 ..]
+    }
+
+    /// Moves position to the begin of the next byte if it is not aligned
+    #[inline]
+    pub fn align(&mut self) {
+        self.position = (self.position + 7) / 8 * 8;
+    }
+
+    // error for the read out of the array
+    fn too_short(&self, bits: usize) -> BitWrapError {
+        let needed = self.position.saturating_add(bits).saturating_add(7) / 8 - self.position / 8;
+        BitWrapError::new(BitWrapErrorKind::BufferTooShort { needed })
+            .at_offset(self.position)
+    }
+
+    /// Skips `bits` without reading
+    pub fn skip(&mut self, bits: usize) -> Result<(), BitWrapError> {
+        if bits > self.remaining_bits() {
+            return Err(self.too_short(bits));
+        }
+
+        self.position += bits;
+        Ok(())
+    }
+
+    /// Reads value with size up to 128 bits
+    pub fn read_bits(&mut self, bits: usize) -> Result<u128, BitWrapError> {
+        assert!(bits <= 128, "bit reader could not read more than 128 bits");
+
+        if bits > self.remaining_bits() {
+            return Err(self.too_short(bits));
+        }
+
+        let mut value: u128 = 0;
+        let mut done = 0;
+
+        while done < bits {
+            let byte = self.src[self.position / 8];
+            let used = self.position % 8;
+            let size = (8 - used).min(bits - done);
+
+            if self.lsb0 {
+                let chunk = (byte >> used) & mask(size);
+                value |= u128::from(chunk) << done;
+            } else {
+                let chunk = (byte >> (8 - used - size)) & mask(size);
+                value = (value << size) | u128::from(chunk);
+            }
+
+            done += size;
+            self.position += size;
+        }
+
+        Ok(value)
+    }
+
+    /// Reads `len` bytes. Position should be at the byte boundary
+    ///
+    /// # Panics
+    ///
+    /// Panics if position is not aligned
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], BitWrapError> {
+        assert!(self.position % 8 == 0, "bit reader position should be aligned to read bytes");
+
+        if len > self.remaining_bits() / 8 {
+            return Err(self.too_short(len.saturating_mul(8)));
+        }
+
+        let begin = self.position / 8;
+        self.position += len * 8;
+        Ok(&self.src[begin .. begin + len])
+    }
+
+    /// Reads object with fixed size at any bit position
+    pub fn read<T: BitWrapBits>(&mut self) -> Result<T, T::Error> {
+        let value = T::unpack_bits(self.remaining(), self.position % 8)
+            .map_err(|e| e.at_offset(self.position))?;
+        self.position += T::BITS;
+        Ok(value)
+    }
+
+    /// Reads object with `unpack_from` from the rest of the array.
+    /// Position should be at the byte boundary
+    ///
+    /// # Panics
+    ///
+    /// Panics if position is not aligned
+    pub fn unpack<T: BitWrapExt>(&mut self) -> Result<T, T::Error> {
+        assert!(self.position % 8 == 0, "bit reader position should be aligned to unpack object");
+
+        let (value, len) = T::unpack_from(self.remaining())
+            .map_err(|e| e.at_offset(self.position))?;
+        self.position += len * 8;
+        Ok(value)
+    }
+}
+
+
+/// Writes bits into the byte array.
+///
+/// Byte is cleared when the first bit is written into it.
+/// Bit order is the same as in the `BitReader`
+#[derive(Debug)]
+pub struct BitWriter<'a> {
+    dst: &'a mut [u8],
+    position: usize,
+    lsb0: bool,
+}
+
+
+impl<'a> BitWriter<'a> {
+    /// Creates writer with `msb0` bit order
+    pub fn new(dst: &'a mut [u8]) -> Self {
+        Self {
+            dst,
+            position: 0,
+            lsb0: false,
+        }
+    }
+
+    /// Creates writer with `lsb0` bit order
+    pub fn new_lsb0(dst: &'a mut [u8]) -> Self {
+        Self {
+            dst,
+            position: 0,
+            lsb0: true,
+        }
+    }
+
+    /// Returns number of bits written from the begin of the array
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns number of bits left in the array
+    #[inline]
+    pub fn remaining_bits(&self) -> usize {
+        self.dst.len() * 8 - self.position
+    }
+
+    /// Moves position to the begin of the next byte if it is not aligned.
+    /// Rest of the current byte is zero
+    #[inline]
+    pub fn align(&mut self) {
+        self.position = (self.position + 7) / 8 * 8;
+    }
+
+    // error for the write out of the array
+    fn too_short(&self, bits: usize) -> BitWrapError {
+        let needed = self.position.saturating_add(bits).saturating_add(7) / 8 - self.position / 8;
+        BitWrapError::new(BitWrapErrorKind::BufferTooShort { needed })
+            .at_offset(self.position)
+    }
+
+    /// Skips `bits` keeping their values
+    pub fn skip(&mut self, bits: usize) -> Result<(), BitWrapError> {
+        if bits > self.remaining_bits() {
+            return Err(self.too_short(bits));
+        }
+
+        self.position += bits;
+        Ok(())
+    }
+
+    /// Writes value with size up to 128 bits.
+    /// Returns `InvalidValue` error if value does not fit into the size
+    pub fn write_bits(&mut self, bits: usize, value: u128) -> Result<(), BitWrapError> {
+        assert!(bits <= 128, "bit writer could not write more than 128 bits");
+
+        if bits < 128 && value >> bits != 0 {
+            return Err(BitWrapError::new(BitWrapErrorKind::InvalidValue).at_offset(self.position));
+        }
+
+        if bits > self.remaining_bits() {
+            return Err(self.too_short(bits));
+        }
+
+        let mut done = 0;
+
+        while done < bits {
+            let index = self.position / 8;
+            let used = self.position % 8;
+            let size = (8 - used).min(bits - done);
+
+            if used == 0 {
+                self.dst[index] = 0;
+            }
+
+            if self.lsb0 {
+                let chunk = (value >> done) as u8 & mask(size);
+                self.dst[index] |= chunk << used;
+            } else {
+                let chunk = (value >> (bits - done - size)) as u8 & mask(size);
+                self.dst[index] |= chunk << (8 - used - size);
+            }
+
+            done += size;
+            self.position += size;
+        }
+
+        Ok(())
+    }
+
+    /// Writes bytes. Position should be at the byte boundary
+    ///
+    /// # Panics
+    ///
+    /// Panics if position is not aligned
+    pub fn write_bytes(&mut self, src: &[u8]) -> Result<(), BitWrapError> {
+        assert!(self.position % 8 == 0, "bit writer position should be aligned to write bytes");
+
+        if src.len() > self.remaining_bits() / 8 {
+            return Err(self.too_short(src.len().saturating_mul(8)));
+        }
+
+        let begin = self.position / 8;
+        self.dst[begin .. begin + src.len()].copy_from_slice(src);
+        self.position += src.len() * 8;
+        Ok(())
+    }
+
+    /// Copies `bits` from the reader
+    pub fn copy_from(&mut self, src: &mut BitReader<'_>, bits: usize) -> Result<(), BitWrapError> {
+        let mut done = 0;
+
+        while done < bits {
+            let size = (bits - done).min(128);
+            let value = src.read_bits(size)?;
+            self.write_bits(size, value)?;
+            done += size;
+        }
+
+        Ok(())
+    }
+
+    /// Writes object with fixed size at any bit position
+    pub fn write<T: BitWrapBits>(&mut self, value: &T) -> Result<(), T::Error> {
+        let position = self.position;
+        value.pack_bits(&mut self.dst[position / 8 ..], position % 8)
+            .map_err(|e| e.at_offset(position))?;
+        self.position += T::BITS;
+        Ok(())
+    }
+
+    /// Writes object with `pack` into the rest of the array.
+    /// Position should be at the byte boundary
+    ///
+    /// # Panics
+    ///
+    /// Panics if position is not aligned
+    pub fn pack<T: BitWrapExt>(&mut self, value: &T) -> Result<(), T::Error> {
+        assert!(self.position % 8 == 0, "bit writer position should be aligned to pack object");
+
+        let position = self.position;
+        let len = value.pack(&mut self.dst[position / 8 ..])
+            .map_err(|e| e.at_offset(position))?;
+        self.position += len * 8;
+        Ok(())
+    }
+}
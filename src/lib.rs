@@ -14,22 +14,140 @@ use {
     core::{
         fmt,
         convert::Infallible,
+        num::TryFromIntError,
+        str::Utf8Error,
     },
 };
 
 
+mod bits;
+
+
 pub use {
     bitwrap_derive::BitWrap,
+    bits::{
+        BitReader,
+        BitWriter,
+    },
 };
 
 
-#[derive(Debug, PartialEq)]
-pub struct BitWrapError;
+/// Kind of the pack or unpack error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitWrapErrorKind {
+    /// Buffer is shorter than required. Contains required buffer length in bytes
+    BufferTooShort { needed: usize },
+    /// Value could not be converted into the field type or bits
+    InvalidValue,
+    /// String has invalid UTF-8 sequence
+    InvalidUtf8,
+    /// Value does not match constraint defined for the field
+    Constraint,
+    /// Data left after the unpacked object
+    TrailingData,
+}
+
+
+impl fmt::Display for BitWrapErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BitWrapErrorKind::BufferTooShort { needed } => {
+                write!(f, "buffer too short, {} bytes needed", needed)
+            }
+            BitWrapErrorKind::InvalidValue => write!(f, "invalid value"),
+            BitWrapErrorKind::InvalidUtf8 => write!(f, "invalid UTF-8"),
+            BitWrapErrorKind::Constraint => write!(f, "constraint violation"),
+            BitWrapErrorKind::TrailingData => write!(f, "trailing data"),
+        }
+    }
+}
+
+
+/// Error with kind and location of the field where it occurred
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitWrapError {
+    kind: BitWrapErrorKind,
+    struct_name: &'static str,
+    field_name: &'static str,
+    position: usize,
+}
+
+
+impl BitWrapError {
+    /// Creates error without location
+    pub const fn new(kind: BitWrapErrorKind) -> Self {
+        Self {
+            kind,
+            struct_name: "",
+            field_name: "",
+            position: 0,
+        }
+    }
+
+    /// Sets the field name if it is not defined yet and moves
+    /// error position by the field offset in bits.
+    /// Used by the derive and custom implementations for nested fields
+    pub fn at_field(mut self, struct_name: &'static str, field_name: &'static str, offset: usize) -> Self {
+        if self.struct_name.is_empty() {
+            self.struct_name = struct_name;
+            self.field_name = field_name;
+        }
+        self.at_offset(offset)
+    }
+
+    /// Moves error position by offset in bits
+    pub fn at_offset(mut self, offset: usize) -> Self {
+        self.position = self.position.saturating_add(offset);
+        if let BitWrapErrorKind::BufferTooShort { needed } = &mut self.kind {
+            *needed = needed.saturating_add(offset / 8);
+        }
+        self
+    }
+
+    /// Returns kind of the error
+    #[inline]
+    pub fn kind(&self) -> BitWrapErrorKind {
+        self.kind
+    }
+
+    /// Returns name of the struct where error occurred
+    #[inline]
+    pub fn struct_name(&self) -> Option<&'static str> {
+        Some(self.struct_name).filter(|v| ! v.is_empty())
+    }
+
+    /// Returns name of the field where error occurred
+    #[inline]
+    pub fn field_name(&self) -> Option<&'static str> {
+        Some(self.field_name).filter(|v| ! v.is_empty())
+    }
+
+    /// Returns offset in bytes from the begin of the buffer
+    #[inline]
+    pub fn byte_offset(&self) -> usize {
+        self.position / 8
+    }
+
+    /// Returns offset in bits from the begin of the buffer
+    #[inline]
+    pub fn bit_offset(&self) -> usize {
+        self.position
+    }
+}
 
 
 impl fmt::Display for BitWrapError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "index out of bounds")
+        write!(f, "{}", self.kind)?;
+
+        if ! self.struct_name.is_empty() {
+            write!(f, " at {}", self.struct_name)?;
+            if ! self.field_name.is_empty() {
+                write!(f, ".{}", self.field_name)?;
+            }
+        }
+
+        write!(f, " (byte {}, bit {})", self.position / 8, self.position % 8)
     }
 }
 
@@ -38,6 +156,14 @@ impl fmt::Display for BitWrapError {
 impl std::error::Error for BitWrapError {}
 
 
+impl From<BitWrapErrorKind> for BitWrapError {
+    #[inline]
+    fn from(kind: BitWrapErrorKind) -> BitWrapError {
+        BitWrapError::new(kind)
+    }
+}
+
+
 impl From<Infallible> for BitWrapError {
     fn from(x: Infallible) -> BitWrapError {
         match x {}
@@ -45,17 +171,142 @@ impl From<Infallible> for BitWrapError {
 }
 
 
+impl From<TryFromIntError> for BitWrapError {
+    #[inline]
+    fn from(_: TryFromIntError) -> BitWrapError {
+        BitWrapError::new(BitWrapErrorKind::InvalidValue)
+    }
+}
+
+
+impl From<Utf8Error> for BitWrapError {
+    #[inline]
+    fn from(_: Utf8Error) -> BitWrapError {
+        BitWrapError::new(BitWrapErrorKind::InvalidUtf8)
+    }
+}
+
+
+/// Error type returned by pack and unpack.
+/// Custom error should be convertible from BitWrapError and could keep
+/// location of the field by overriding default methods
+pub trait BitWrapErrorExt: From<BitWrapError> {
+    /// Sets location of the field. Default implementation does nothing
+    #[inline]
+    fn at_field(self, _struct_name: &'static str, _field_name: &'static str, _offset: usize) -> Self {
+        self
+    }
+
+    /// Moves error position by offset in bits. Default implementation does nothing
+    #[inline]
+    fn at_offset(self, _offset: usize) -> Self {
+        self
+    }
+}
+
+
+impl BitWrapErrorExt for BitWrapError {
+    #[inline]
+    fn at_field(self, struct_name: &'static str, field_name: &'static str, offset: usize) -> Self {
+        BitWrapError::at_field(self, struct_name, field_name, offset)
+    }
+
+    #[inline]
+    fn at_offset(self, offset: usize) -> Self {
+        BitWrapError::at_offset(self, offset)
+    }
+}
+
+
 pub trait BitWrapExt {
+    /// Error returned by pack and unpack
+    type Error: BitWrapErrorExt;
+
     /// Build byte array
-    fn pack(&self, dst: &mut [u8]) -> Result<usize, BitWrapError>;
+    fn pack(&self, dst: &mut [u8]) -> Result<usize, Self::Error>;
+
+    /// Number of bytes required to pack object
+    fn packed_len(&self) -> usize;
+
+    /// Build byte vector with size returned by `packed_len`
+    #[cfg(feature = "std")]
+    fn pack_to_vec(&self) -> Result<Vec<u8>, Self::Error> {
+        let mut buffer = vec![0; self.packed_len()];
+        let len = self.pack(&mut buffer)?;
+        buffer.truncate(len);
+        Ok(buffer)
+    }
 
     /// Extract object field values from byte array
-    fn unpack(&mut self, src: &[u8]) -> Result<usize, BitWrapError>;
+    fn unpack(&mut self, src: &[u8]) -> Result<usize, Self::Error>;
+
+    /// Build new object from byte array.
+    /// Returns object and number of bytes used
+    fn unpack_from(src: &[u8]) -> Result<(Self, usize), Self::Error>
+    where
+        Self: Sized;
+}
+
+
+/// Object packed at any bit position.
+/// Implemented by the derive for structs without variable-length fields,
+/// including structs with size that is not a multiple of 8 bits
+pub trait BitWrapBits: Sized {
+    /// Error returned by pack and unpack
+    type Error: BitWrapErrorExt;
+
+    /// Packed size in bits
+    const BITS: usize;
+
+    /// Pack object into `dst` starting from `bit` offset.
+    /// Bits before `bit` in the first byte are kept, next bits should be zero.
+    /// Error location is relative to the begin of the object
+    fn pack_bits(&self, dst: &mut [u8], bit: usize) -> Result<(), Self::Error>;
+
+    /// Build new object from `src` starting from `bit` offset
+    fn unpack_bits(src: &[u8], bit: usize) -> Result<Self, Self::Error>;
+}
+
+
+/// Object with size known at compile time.
+/// Implemented by the derive for structs without variable-length fields
+pub trait BitWrapSized: BitWrapExt + BitWrapBits {
+    /// Packed size in bytes
+    const SIZE: usize = <Self as BitWrapBits>::BITS / 8;
+}
+
+
+/// Enum with variant selected by the tag value from the previous field
+pub trait BitWrapTag<T>: BitWrapExt {
+    /// Get tag value of the current variant
+    fn tag(&self) -> Result<T, Self::Error>;
+
+    /// Select variant by the tag value and extract its fields from byte array
+    fn unpack_tag(&mut self, tag: T, src: &[u8]) -> Result<usize, Self::Error>;
+
+    /// Build new variant selected by the tag value from byte array
+    fn unpack_tag_from(tag: T, src: &[u8]) -> Result<(Self, usize), Self::Error>
+    where
+        Self: Sized;
+}
+
+
+/// Collection with number of items from the previous field
+pub trait BitWrapCount: BitWrapExt {
+    /// Get number of items
+    fn count(&self) -> usize;
+
+    /// Build collection with `count` items from byte array
+    fn unpack_count(count: usize, src: &[u8]) -> Result<(Self, usize), Self::Error>
+    where
+        Self: Sized;
 }
 
 
 #[cfg(feature = "std")]
 impl BitWrapExt for Vec<u8> {
+    type Error = BitWrapError;
+
     #[inline]
     fn pack(&self, dst: &mut [u8]) -> Result<usize, BitWrapError> {
         let len = self.len();
@@ -63,44 +314,108 @@ impl BitWrapExt for Vec<u8> {
             dst[.. len].clone_from_slice(self.as_slice());
             Ok(len)
         } else {
-            Err(BitWrapError)
+            Err(BitWrapErrorKind::BufferTooShort { needed: len }.into())
         }
     }
 
+    #[inline]
+    fn packed_len(&self) -> usize {
+        self.len()
+    }
+
     #[inline]
     fn unpack(&mut self, src: &[u8]) -> Result<usize, BitWrapError> {
         self.extend_from_slice(src);
         Ok(src.len())
     }
+
+    #[inline]
+    fn unpack_from(src: &[u8]) -> Result<(Self, usize), BitWrapError> {
+        Ok((src.to_vec(), src.len()))
+    }
 }
 
 
+// unpacks collection item at `skip` bytes.
+// item should use at least one byte and no more than the rest of the array
 #[cfg(feature = "std")]
-impl<T: BitWrapExt + Default> BitWrapExt for Vec<T> {
+fn unpack_item<T: BitWrapExt>(src: &[u8], skip: usize) -> Result<(T, usize), T::Error> {
+    let rest = &src[skip ..];
+    let (item, len) = T::unpack_from(rest)
+        .map_err(|e| e.at_offset(skip * 8))?;
+
+    if len == 0 || len > rest.len() {
+        let error = BitWrapError::new(BitWrapErrorKind::InvalidValue).at_offset(skip * 8);
+        return Err(error.into());
+    }
+
+    Ok((item, len))
+}
+
+
+#[cfg(feature = "std")]
+impl<T: BitWrapExt> BitWrapExt for Vec<T> {
+    type Error = T::Error;
+
     #[inline]
-    fn pack(&self, dst: &mut [u8]) -> Result<usize, BitWrapError> {
+    fn pack(&self, dst: &mut [u8]) -> Result<usize, Self::Error> {
         let mut skip = 0;
         for item in self {
-            skip += item.pack(&mut dst[skip ..])?;
+            skip += item.pack(&mut dst[skip ..])
+                .map_err(|e| e.at_offset(skip * 8))?;
         }
         Ok(skip)
     }
 
     #[inline]
-    fn unpack(&mut self, src: &[u8]) -> Result<usize, BitWrapError> {
+    fn packed_len(&self) -> usize {
+        self.iter().map(BitWrapExt::packed_len).sum()
+    }
+
+    #[inline]
+    fn unpack(&mut self, src: &[u8]) -> Result<usize, Self::Error> {
         let mut skip = 0;
         while skip < src.len() {
-            let mut item = T::default();
-            skip += item.unpack(&src[skip ..])?;
+            let (item, len) = unpack_item::<T>(src, skip)?;
+            skip += len;
             self.push(item);
         }
         Ok(skip)
     }
+
+    #[inline]
+    fn unpack_from(src: &[u8]) -> Result<(Self, usize), Self::Error> {
+        let mut value: Vec<T> = Vec::new();
+        let len = value.unpack(src)?;
+        Ok((value, len))
+    }
+}
+
+
+#[cfg(feature = "std")]
+impl<T: BitWrapExt> BitWrapCount for Vec<T> {
+    #[inline]
+    fn count(&self) -> usize {
+        self.len()
+    }
+
+    fn unpack_count(count: usize, src: &[u8]) -> Result<(Self, usize), Self::Error> {
+        let mut value = Vec::new();
+        let mut skip = 0;
+        for _ in 0 .. count {
+            let (item, len) = unpack_item::<T>(src, skip)?;
+            skip += len;
+            value.push(item);
+        }
+        Ok((value, skip))
+    }
 }
 
 
 #[cfg(feature = "std")]
 impl BitWrapExt for String {
+    type Error = BitWrapError;
+
     #[inline]
     fn pack(&self, dst: &mut [u8]) -> Result<usize, BitWrapError> {
         let s = self.as_bytes();
@@ -109,18 +424,25 @@ impl BitWrapExt for String {
             dst[.. len].clone_from_slice(s);
             Ok(len)
         } else {
-            Err(BitWrapError)
+            Err(BitWrapErrorKind::BufferTooShort { needed: len }.into())
         }
     }
 
     #[inline]
-    fn unpack(&mut self, src: &[u8]) -> Result<usize, BitWrapError> {
-        let s = match std::str::from_utf8(src) {
-            Ok(v) => v,
-            Err(_) => return Err(BitWrapError),
-        };
+    fn packed_len(&self) -> usize {
+        self.len()
+    }
 
+    #[inline]
+    fn unpack(&mut self, src: &[u8]) -> Result<usize, BitWrapError> {
+        let s = std::str::from_utf8(src)?;
         self.push_str(s);
         Ok(src.len())
     }
+
+    #[inline]
+    fn unpack_from(src: &[u8]) -> Result<(Self, usize), BitWrapError> {
+        let s = std::str::from_utf8(src)?;
+        Ok((s.to_owned(), src.len()))
+    }
 }
@@ -7,6 +7,8 @@ use {
     },
 };
 
+mod common;
+
 
 #[test]
 fn test_array() {
@@ -30,4 +32,6 @@ fn test_array() {
 
     assert_eq!(result, DATA.len());
     assert_eq!(buffer, DATA);
+
+    common::unpack_random::<Packet>(&[DATA]);
 }
@@ -0,0 +1,74 @@
+use bitwrap::*;
+
+
+#[test]
+fn test_bit_order_lsb0() {
+    #[derive(Default, Debug, PartialEq, BitWrap)]
+    #[bitwrap(bit_order = lsb0)]
+    struct Packet {
+        #[bitfield(1)]
+        flag: bool,
+        #[bitfield(3)]
+        kind: u8,
+        #[bitfield(4)]
+        version: u8,
+        #[bitfield(4)]
+        id: u8,
+        #[bitfield(12)]
+        value: u16,
+    }
+
+    const DATA: &[u8] = &[0xCB, 0xA5, 0xBC];
+
+    let mut packet = Packet::default();
+    let result = packet.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(packet, Packet {
+        flag: true,
+        kind: 0b101,
+        version: 0x0C,
+        id: 0x05,
+        value: 0xABC,
+    });
+
+    let mut buffer: [u8; 3] = [0; 3];
+    let result = packet.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+}
+
+
+#[test]
+fn test_bit_order_lsb0_little() {
+    // Intel byte order for CAN signals
+    #[derive(Default, Debug, PartialEq, BitWrap)]
+    #[bitwrap(endian = little, bit_order = lsb0)]
+    struct Signal {
+        #[bitfield(4)]
+        mode: u8,
+        #[bitfield(12)]
+        speed: u16,
+        #[bitfield(16)]
+        rpm: u16,
+    }
+
+    const DATA: &[u8] = &[0xC5, 0xAB, 0x34, 0x12];
+
+    let mut signal = Signal::default();
+    let result = signal.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(signal, Signal {
+        mode: 0x05,
+        speed: 0xABC,
+        rpm: 0x1234,
+    });
+
+    let mut buffer: [u8; 4] = [0; 4];
+    let result = signal.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+}
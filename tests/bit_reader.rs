@@ -0,0 +1,145 @@
+use bitwrap::*;
+
+mod common;
+
+
+#[derive(Debug, PartialEq, BitWrap)]
+struct Pid {
+    #[bitfield(3, name = _reserved, value = 0b111)]
+    #[bitfield(13)]
+    pid: u16,
+}
+
+
+// hand-written implementation with nested derived struct
+#[derive(Debug, PartialEq)]
+struct Header {
+    version: u8,
+    pid: Pid,
+    payload: Vec<u8>,
+}
+
+
+impl BitWrapExt for Header {
+    type Error = BitWrapError;
+
+    fn pack(&self, dst: &mut [u8]) -> Result<usize, BitWrapError> {
+        let mut writer = BitWriter::new(dst);
+        writer.write_bits(4, u128::from(self.version))?;
+        writer.write(&self.pid)?;
+        writer.align();
+        writer.write_bytes(&self.payload)?;
+        Ok(writer.position() / 8)
+    }
+
+    fn packed_len(&self) -> usize {
+        3 + self.payload.len()
+    }
+
+    fn unpack(&mut self, src: &[u8]) -> Result<usize, BitWrapError> {
+        let (value, len) = Self::unpack_from(src)?;
+        *self = value;
+        Ok(len)
+    }
+
+    fn unpack_from(src: &[u8]) -> Result<(Self, usize), BitWrapError> {
+        let mut reader = BitReader::new(src);
+        let version = reader.read_bits(4)? as u8;
+        let pid = reader.read::<Pid>()?;
+        reader.align();
+        let payload = reader.read_bytes(reader.remaining_bits() / 8)?.to_vec();
+        Ok((Self { version, pid, payload }, reader.position() / 8))
+    }
+}
+
+
+#[test]
+fn test_bit_reader() {
+    const DATA: &[u8] = &[0xA5, 0x3C];
+
+    let mut reader = BitReader::new(DATA);
+    assert_eq!(reader.read_bits(3), Ok(0b101));
+    assert_eq!(reader.read_bits(9), Ok(0b0_0101_0011));
+    assert_eq!(reader.position(), 12);
+    assert_eq!(reader.remaining_bits(), 4);
+
+    let error = reader.read_bits(5).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::BufferTooShort { needed: 3 });
+    assert_eq!(error.bit_offset(), 12);
+
+    reader.align();
+    assert_eq!(reader.remaining_bits(), 0);
+
+    let mut reader = BitReader::new_lsb0(DATA);
+    assert_eq!(reader.read_bits(3), Ok(0b101));
+    assert_eq!(reader.read_bits(9), Ok(0b1100_10100));
+    assert_eq!(reader.read_bits(4), Ok(0b0011));
+
+    let mut reader = BitReader::new(DATA);
+    assert_eq!(reader.read_bytes(1), Ok(&DATA[.. 1]));
+    assert_eq!(reader.read_bytes(2).unwrap_err().kind(), BitWrapErrorKind::BufferTooShort { needed: 3 });
+}
+
+
+#[test]
+fn test_bit_writer() {
+    let mut buffer = [0xFF; 3];
+
+    let mut writer = BitWriter::new(&mut buffer);
+    writer.write_bits(3, 0b101).unwrap();
+    writer.write_bits(9, 0b0_0101_0011).unwrap();
+    writer.write_bits(4, 0b1100).unwrap();
+    assert_eq!(writer.position(), 16);
+
+    let error = writer.write_bits(4, 0x10).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.bit_offset(), 16);
+
+    let error = writer.write_bits(12, 0).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::BufferTooShort { needed: 4 });
+
+    writer.write_bits(1, 1).unwrap();
+    writer.align();
+    assert_eq!(writer.remaining_bits(), 0);
+    assert_eq!(buffer, [0xA5, 0x3C, 0x80]);
+
+    let mut buffer = [0; 2];
+    let mut writer = BitWriter::new_lsb0(&mut buffer);
+    writer.write_bits(3, 0b101).unwrap();
+    writer.write_bits(9, 0b1100_10100).unwrap();
+    writer.write_bits(4, 0b0011).unwrap();
+    assert_eq!(buffer, [0xA5, 0x3C]);
+}
+
+
+#[test]
+fn test_bit_reader_derive() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Packet {
+        #[bitfield(8)]
+        kind: u8,
+        #[bitfield]
+        header: Header,
+    }
+
+    let packet = Packet {
+        kind: 1,
+        header: Header {
+            version: 5,
+            pid: Pid { pid: 0x100 },
+            payload: vec![0xAA],
+        },
+    };
+
+    const DATA: &[u8] = &[0x01, 0x5E, 0x10, 0x00, 0xAA];
+
+    assert_eq!(packet.pack_to_vec().unwrap(), DATA);
+    assert_eq!(Packet::unpack_from(DATA), Ok((packet, DATA.len())));
+
+    // nested error location
+    let error = Packet::unpack_from(&DATA[.. 2]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::BufferTooShort { needed: 4 });
+    assert_eq!(error.bit_offset(), 12);
+
+    common::unpack_random::<Packet>(&[DATA]);
+}
@@ -1,5 +1,7 @@
 use bitwrap::*;
 
+mod common;
+
 
 #[test]
 fn test_bits() {
@@ -36,6 +38,8 @@ fn test_bits() {
 
     assert_eq!(result, DATA.len());
     assert_eq!(&buffer[.. result], DATA);
+
+    common::unpack_random::<Packet>(&[DATA]);
 }
 
 
@@ -49,8 +53,9 @@ fn test_overflow() {
     }
 
     let mut packet = Packet::default();
-    match packet.unpack(DATA) {
-        Err(BitWrapError) => {}
-        _ => unreachable!(),
-    };
+    let error = packet.unpack(DATA).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::BufferTooShort { needed: 4 });
+    assert_eq!(error.field_name(), Some("value"));
+
+    common::unpack_random::<Packet>(&[DATA]);
 }
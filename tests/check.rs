@@ -0,0 +1,53 @@
+use bitwrap::*;
+
+
+#[test]
+fn test_check() {
+    #[derive(Default, Debug, BitWrap)]
+    struct Packet {
+        #[bitfield(8, name = _sync, value = 0x47, check)]
+        #[bitfield(2, name = _reserved, value = 0b11, check)]
+        #[bitfield(6)]
+        version: u8,
+    }
+
+    let mut packet = Packet::default();
+    assert_eq!(packet.unpack(&[0x47, 0xC5]), Ok(2));
+    assert_eq!(packet.version, 5);
+
+    assert_eq!(
+        packet.unpack(&[0x48, 0xC5]),
+        Err(BitWrapError::new(BitWrapErrorKind::Constraint).at_field("Packet", "_sync", 0))
+    );
+
+    let error = packet.unpack(&[0x47, 0x45]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::Constraint);
+    assert_eq!(error.field_name(), Some("_reserved"));
+    assert_eq!(error.bit_offset(), 8);
+    assert_eq!(error.to_string(), "constraint violation at Packet._reserved (byte 1, bit 0)");
+}
+
+
+#[test]
+fn test_check_lenient() {
+    #[derive(Default, Debug, BitWrap)]
+    struct Packet {
+        #[bitfield(4, name = _reserved, value = 0xF, check = invalid_reserved)]
+        #[bitfield(12)]
+        pid: u16,
+        invalid_reserved: bool,
+    }
+
+    let mut packet = Packet::default();
+    assert_eq!(packet.unpack(&[0x71, 0x00]), Ok(2));
+    assert_eq!(packet.pid, 0x100);
+    assert!(packet.invalid_reserved);
+
+    assert_eq!(packet.unpack(&[0xF1, 0x00]), Ok(2));
+    assert!(! packet.invalid_reserved);
+
+    // pack writes declared value
+    let mut buffer: [u8; 2] = [0; 2];
+    assert_eq!(packet.pack(&mut buffer), Ok(2));
+    assert_eq!(buffer, [0xF1, 0x00]);
+}
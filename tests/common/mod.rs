@@ -0,0 +1,103 @@
+// randomized unpack of the test structs.
+// each test crate uses only part of the helpers
+#![allow(dead_code)]
+
+// no_std test crates use the helpers too
+extern crate alloc;
+
+use {
+    alloc::vec::Vec,
+    bitwrap::*,
+};
+
+
+// number of random buffers and mutations of each sample
+const ROUNDS: usize = 512;
+
+
+// xorshift generator with fixed seed to reproduce failures
+struct Random(u64);
+
+
+impl Random {
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    fn below(&mut self, max: usize) -> usize {
+        (self.next_u64() % max as u64) as usize
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0 .. len).map(|_| self.next_u64() as u8).collect()
+    }
+}
+
+
+// random buffers, truncated samples and samples with random bytes
+fn inputs(samples: &[&[u8]]) -> Vec<Vec<u8>> {
+    let mut random = Random(0x2545_F491_4F6C_DD1D);
+    let mut result = Vec::new();
+
+    for _ in 0 .. ROUNDS {
+        let len = random.below(64);
+        result.push(random.bytes(len));
+    }
+
+    for sample in samples {
+        for len in 0 .. sample.len() {
+            result.push(sample[.. len].to_vec());
+        }
+
+        for _ in 0 .. ROUNDS {
+            let mut data = sample.to_vec();
+
+            for _ in 0 .. 1 + random.below(4) {
+                if data.is_empty() {
+                    break;
+                }
+                let index = random.below(data.len());
+                data[index] = random.next_u64() as u8;
+            }
+
+            match random.below(4) {
+                0 => data.truncate(random.below(data.len() + 1)),
+                1 => {
+                    let len = random.below(16);
+                    data.extend(random.bytes(len));
+                }
+                _ => {}
+            }
+
+            result.push(data);
+        }
+    }
+
+    result
+}
+
+
+// unpack should return error on malformed input instead of panic
+// and should not use more bytes than available
+pub fn unpack_random<T: BitWrapExt>(samples: &[&[u8]]) {
+    for data in inputs(samples) {
+        if let Ok((_, len)) = T::unpack_from(&data) {
+            assert!(len <= data.len(), "unpacked {} bytes from {:02X?}", len, data);
+        }
+    }
+}
+
+
+// unpack at any bit position should return error instead of panic
+pub fn unpack_bits_random<T: BitWrapBits>(samples: &[&[u8]]) {
+    for data in inputs(samples) {
+        for bit in 0 .. 8 {
+            let _ = T::unpack_bits(&data, bit);
+        }
+    }
+}
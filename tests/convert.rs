@@ -8,6 +8,8 @@ use {
     bitwrap::*,
 };
 
+mod common;
+
 
 #[test]
 fn test_readme_convert() {
@@ -34,7 +36,7 @@ fn test_readme_convert() {
                 2 => Ok(Coffee::Cappuccino),
                 3 => Ok(Coffee::Espresso),
                 4 => Ok(Coffee::Americano),
-                _ => Err(BitWrapError),
+                _ => Err(BitWrapErrorKind::InvalidValue.into()),
             }
         }
     }
@@ -71,4 +73,6 @@ fn test_readme_convert() {
     let result = packet.pack(&mut buffer).unwrap();
 
     assert_eq!(&buffer[.. result], DATA);
+
+    common::unpack_random::<Packet>(&[DATA]);
 }
@@ -0,0 +1,86 @@
+use bitwrap::*;
+
+mod common;
+
+
+#[derive(Debug, PartialEq, BitWrap)]
+struct Item {
+    #[bitfield(8)] id: u8,
+    #[bitfield(8, name = name_len, value = self.name.len())]
+    #[bitfield(name_len)]
+    name: Vec<u8>,
+}
+
+
+#[test]
+fn test_count() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct List {
+        #[bitfield(4, name = _reserved, value = 0)]
+        #[bitfield(4, name = n_items)]
+        #[bitfield(count = n_items)]
+        items: Vec<Item>,
+        #[bitfield(8)] tail: u8,
+    }
+
+    let list = List {
+        items: vec![
+            Item { id: 1, name: b"ab".to_vec() },
+            Item { id: 2, name: Vec::new() },
+        ],
+        tail: 0xFF,
+    };
+
+    const DATA: &[u8] = &[0x02, 0x01, 0x02, b'a', b'b', 0x02, 0x00, 0xFF];
+
+    assert_eq!(list.pack_to_vec().unwrap(), DATA);
+    assert_eq!(List::unpack_from(DATA), Ok((list, DATA.len())));
+
+    // not enough items
+    let error = List::unpack_from(&[0x03, 0x01, 0x00, 0x02, 0x00]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::BufferTooShort { needed: 6 });
+    assert_eq!(error.field_name(), Some("id"));
+    assert_eq!(error.byte_offset(), 5);
+
+    // count does not fit into the field
+    let list = List {
+        items: (0 .. 16).map(|id| Item { id, name: Vec::new() }).collect(),
+        tail: 0,
+    };
+    let error = list.pack_to_vec().unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.field_name(), Some("n_items"));
+    assert_eq!(error.bit_offset(), 4);
+
+    common::unpack_random::<List>(&[DATA]);
+}
+
+
+#[test]
+fn test_count_field() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct List {
+        #[bitfield(8)] n_items: u8,
+        #[bitfield(8, name = len, length_of = items)]
+        #[bitfield(count = n_items)]
+        items: Vec<Item>,
+    }
+
+    // count field is set from the collection
+    let list = List {
+        n_items: 0,
+        items: vec![Item { id: 1, name: Vec::new() }],
+    };
+    let data = list.pack_to_vec().unwrap();
+    assert_eq!(data, [0x01, 0x02, 0x01, 0x00]);
+
+    let (list, _) = List::unpack_from(&data).unwrap();
+    assert_eq!(list.n_items, 1);
+    assert_eq!(list.items.len(), 1);
+
+    // length with more bytes than items
+    let error = List::unpack_from(&[0x01, 0x03, 0x01, 0x00, 0x00]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::TrailingData);
+
+    common::unpack_random::<List>(&[&data]);
+}
@@ -0,0 +1,143 @@
+use {
+    core::convert::TryFrom,
+    bitwrap::*,
+};
+
+mod common;
+
+
+#[derive(Debug, PartialEq)]
+enum MyError {
+    BitWrap(BitWrapError),
+    UnsupportedVersion(u8),
+}
+
+
+impl From<BitWrapError> for MyError {
+    fn from(e: BitWrapError) -> Self {
+        MyError::BitWrap(e)
+    }
+}
+
+
+// conversions with From implementation have Infallible error
+impl From<core::convert::Infallible> for MyError {
+    fn from(x: core::convert::Infallible) -> Self {
+        match x {}
+    }
+}
+
+
+impl BitWrapErrorExt for MyError {
+    fn at_field(self, struct_name: &'static str, field_name: &'static str, offset: usize) -> Self {
+        match self {
+            MyError::BitWrap(e) => MyError::BitWrap(e.at_field(struct_name, field_name, offset)),
+            v => v,
+        }
+    }
+}
+
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+struct Version(u8);
+
+
+impl TryFrom<u8> for Version {
+    type Error = MyError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if value == 2 {
+            Ok(Version(value))
+        } else {
+            Err(MyError::UnsupportedVersion(value))
+        }
+    }
+}
+
+
+impl From<Version> for u8 {
+    fn from(value: Version) -> Self {
+        value.0
+    }
+}
+
+
+#[derive(Debug, Default, PartialEq, Clone, Copy, BitWrap)]
+#[bitwrap(error = MyError)]
+enum Mode {
+    #[default]
+    Fast = 1,
+    Slow = 2,
+}
+
+
+#[derive(Debug, Default, PartialEq, BitWrap)]
+struct Header {
+    #[bitfield(16)]
+    id: u16,
+}
+
+
+#[derive(Debug, Default, PartialEq, BitWrap)]
+#[bitwrap(error = MyError)]
+struct Packet {
+    #[bitfield(4)]
+    version: Version,
+    #[bitfield(4)]
+    mode: Mode,
+    #[bitfield]
+    header: Header,
+}
+
+
+#[test]
+fn test_custom_error() {
+    const DATA: &[u8] = &[0x22, 0x12, 0x34];
+
+    let mut packet = Packet::default();
+    let result = packet.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(packet, Packet {
+        version: Version(2),
+        mode: Mode::Slow,
+        header: Header { id: 0x1234 },
+    });
+
+    let mut buffer: [u8; 3] = [0; 3];
+    let result = packet.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+
+    common::unpack_random::<Packet>(&[DATA]);
+}
+
+
+#[test]
+fn test_custom_error_conversion() {
+    let mut packet = Packet::default();
+    assert_eq!(packet.unpack(&[0x32, 0x12, 0x34]), Err(MyError::UnsupportedVersion(3)));
+
+    let error = packet.unpack(&[0x23, 0x12, 0x34]).unwrap_err();
+    assert_eq!(error, MyError::BitWrap(
+        BitWrapError::new(BitWrapErrorKind::InvalidValue).at_field("Packet", "mode", 4)
+    ));
+
+    assert_eq!(Mode::try_from(3u8), Err(MyError::BitWrap(
+        BitWrapError::new(BitWrapErrorKind::InvalidValue)
+    )));
+}
+
+
+#[test]
+fn test_custom_error_nested() {
+    let mut packet = Packet::default();
+    let error = packet.unpack(&[0x22, 0x12]).unwrap_err();
+
+    assert_eq!(error, MyError::BitWrap(
+        BitWrapError::new(BitWrapErrorKind::BufferTooShort { needed: 2 })
+            .at_field("Header", "id", 0)
+            .at_offset(8)
+    ));
+}
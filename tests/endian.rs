@@ -0,0 +1,75 @@
+use bitwrap::*;
+
+
+#[test]
+fn test_endian_little() {
+    #[derive(Default, Debug, PartialEq, BitWrap)]
+    #[bitwrap(endian = little)]
+    struct Packet {
+        #[bitfield(16)]
+        length: u16,
+        #[bitfield(32)]
+        crc: u32,
+        #[bitfield(16, endian = big)]
+        id: u16,
+    }
+
+    const DATA: &[u8] = &[0x34, 0x12, 0xDD, 0xCC, 0xBB, 0xAA, 0x56, 0x78];
+
+    let mut packet = Packet::default();
+    let result = packet.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(packet, Packet {
+        length: 0x1234,
+        crc: 0xAABBCCDD,
+        id: 0x5678,
+    });
+
+    let mut buffer: [u8; 8] = [0; 8];
+    let result = packet.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+}
+
+
+#[test]
+fn test_endian_not_aligned() {
+    #[derive(Default, Debug, PartialEq, BitWrap)]
+    struct Packet {
+        #[bitfield(4)]
+        version: u8,
+        #[bitfield(12, endian = little)]
+        id: u16,
+        #[bitfield(4)]
+        flags: u8,
+        #[bitfield(20, endian = little)]
+        value: u32,
+        #[bitfield(12, endian = little)]
+        size: u16,
+        #[bitfield(4)]
+        kind: u8,
+    }
+
+    const DATA: &[u8] = &[0xA3, 0x12, 0x5E, 0xCD, 0xAB, 0x23, 0x1F];
+
+    let mut packet = Packet::default();
+    let result = packet.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(packet, Packet {
+        version: 0x0A,
+        id: 0x123,
+        flags: 0x05,
+        value: 0xABCDE,
+        size: 0x123,
+        kind: 0x0F,
+    });
+
+    let mut buffer: [u8; 7] = [0; 7];
+    let result = packet.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+}
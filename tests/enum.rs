@@ -0,0 +1,127 @@
+#![no_std]
+
+use {
+    core::convert::TryFrom,
+    bitwrap::*,
+};
+
+
+#[derive(Debug, Default, PartialEq, Clone, Copy, BitWrap)]
+enum Coffee {
+    #[default]
+    Water,
+    Latte,
+    Cappuccino = 0x0A,
+    Espresso,
+    Americano = 0x100,
+}
+
+
+#[test]
+fn test_enum() {
+    #[derive(Default, BitWrap)]
+    struct Packet {
+        #[bitfield(4, name = _reserved, value = 0)]
+        #[bitfield(4)]
+        coffee: Coffee,
+        #[bitfield(4, name = _reserved, value = 0)]
+        #[bitfield(12)]
+        order: Coffee,
+    }
+
+    const DATA: &[u8] = &[0x0B, 0x01, 0x00];
+
+    let mut packet = Packet::default();
+    let result = packet.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(packet.coffee, Coffee::Espresso);
+    assert_eq!(packet.order, Coffee::Americano);
+
+    let mut buffer: [u8; 3] = [0; 3];
+    let result = packet.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+}
+
+
+#[test]
+fn test_enum_convert() {
+    assert_eq!(Coffee::try_from(0u8), Ok(Coffee::Water));
+    assert_eq!(Coffee::try_from(0x0Au8), Ok(Coffee::Cappuccino));
+    assert_eq!(Coffee::try_from(0x100u16), Ok(Coffee::Americano));
+    assert_eq!(Coffee::try_from(2u8), Err(BitWrapError::new(BitWrapErrorKind::InvalidValue)));
+
+    assert_eq!(u8::try_from(Coffee::Espresso), Ok(0x0B));
+    assert_eq!(u16::try_from(Coffee::Americano), Ok(0x100));
+    assert_eq!(u8::try_from(Coffee::Americano), Err(BitWrapErrorKind::InvalidValue.into()));
+}
+
+
+#[test]
+fn test_enum_unknown() {
+    #[derive(Default, BitWrap)]
+    struct Packet {
+        #[bitfield(8)]
+        coffee: Coffee,
+    }
+
+    let mut packet = Packet::default();
+    assert_eq!(
+        packet.unpack(&[0x05]),
+        Err(BitWrapError::new(BitWrapErrorKind::InvalidValue).at_field("Packet", "coffee", 0))
+    );
+
+    packet.coffee = Coffee::Americano;
+    let mut buffer: [u8; 1] = [0; 1];
+    let error = packet.pack(&mut buffer).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.struct_name(), Some("Packet"));
+    assert_eq!(error.field_name(), Some("coffee"));
+}
+
+
+#[test]
+fn test_enum_catch_all() {
+    #[derive(Debug, PartialEq, Clone, Copy, BitWrap)]
+    #[repr(u8)]
+    enum StreamType {
+        Mpeg2Video = 0x02,
+        Mpeg2Audio = 0x04,
+        H264 = 0x1B,
+        #[bitwrap(unknown)]
+        Other(u8),
+    }
+
+    #[derive(BitWrap)]
+    struct Packet {
+        #[bitfield(8)]
+        stream_type: StreamType,
+        #[bitfield(8)]
+        next_type: StreamType,
+    }
+
+    const DATA: &[u8] = &[0x1B, 0x86];
+
+    let mut packet = Packet {
+        stream_type: StreamType::Mpeg2Video,
+        next_type: StreamType::Mpeg2Video,
+    };
+    let result = packet.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(packet.stream_type, StreamType::H264);
+    assert_eq!(packet.next_type, StreamType::Other(0x86));
+
+    let mut buffer: [u8; 2] = [0; 2];
+    let result = packet.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+
+    assert_eq!(StreamType::try_from(0x04u16), Ok(StreamType::Mpeg2Audio));
+    assert_eq!(StreamType::try_from(0x80u16), Ok(StreamType::Other(0x80)));
+    assert_eq!(StreamType::try_from(0x100u16), Err(BitWrapErrorKind::InvalidValue.into()));
+    assert_eq!(u16::try_from(StreamType::Other(0xFF)), Ok(0xFF));
+}
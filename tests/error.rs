@@ -0,0 +1,84 @@
+use bitwrap::*;
+
+
+#[derive(Debug, Default, PartialEq, Clone, Copy, BitWrap)]
+enum Kind {
+    #[default]
+    Audio = 1,
+    Video = 2,
+}
+
+
+#[derive(Debug, Default, BitWrap)]
+struct Item {
+    #[bitfield(8)]
+    kind: Kind,
+}
+
+
+#[derive(Debug, Default, BitWrap)]
+struct Packet {
+    #[bitfield(4)]
+    version: u8,
+    #[bitfield(12)]
+    kind: Kind,
+    #[bitfield(8, name = items_len, value = self.items.len())]
+    #[bitfield(items_len)]
+    items: Vec<Item>,
+    #[bitfield(8, name = name_len, value = self.name.len())]
+    #[bitfield(name_len)]
+    name: String,
+}
+
+
+#[test]
+fn test_error_bit_offset() {
+    let mut packet = Packet::default();
+    let error = packet.unpack(&[0x10, 0xFF, 0x00, 0x00]).unwrap_err();
+
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.struct_name(), Some("Packet"));
+    assert_eq!(error.field_name(), Some("kind"));
+    assert_eq!(error.byte_offset(), 0);
+    assert_eq!(error.bit_offset(), 4);
+    assert_eq!(error.to_string(), "invalid value at Packet.kind (byte 0, bit 4)");
+}
+
+
+#[test]
+fn test_error_nested() {
+    let mut packet = Packet::default();
+    let error = packet.unpack(&[0x10, 0x01, 0x03, 0x01, 0x02, 0x05, 0x00]).unwrap_err();
+
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.struct_name(), Some("Item"));
+    assert_eq!(error.field_name(), Some("kind"));
+    assert_eq!(error.byte_offset(), 5);
+    assert_eq!(error.to_string(), "invalid value at Item.kind (byte 5, bit 0)");
+}
+
+
+#[test]
+fn test_error_utf8() {
+    let mut packet = Packet::default();
+    let error = packet.unpack(&[0x10, 0x01, 0x00, 0x02, 0xC3, 0x28]).unwrap_err();
+
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidUtf8);
+    assert_eq!(error.field_name(), Some("name"));
+    assert_eq!(error.byte_offset(), 4);
+}
+
+
+#[test]
+fn test_error_buffer() {
+    let mut packet = Packet::default();
+    packet.items.push(Item { kind: Kind::Video });
+    packet.name.push_str("test");
+
+    let mut buffer: [u8; 8] = [0; 8];
+    let error = packet.pack(&mut buffer).unwrap_err();
+
+    assert_eq!(error.kind(), BitWrapErrorKind::BufferTooShort { needed: 9 });
+    assert_eq!(error.field_name(), Some("name"));
+    assert_eq!(error.to_string(), "buffer too short, 9 bytes needed at Packet.name (byte 5, bit 0)");
+}
@@ -0,0 +1,144 @@
+use {
+    core::marker::PhantomData,
+    bitwrap::*,
+};
+
+
+#[derive(Default, Debug, PartialEq, BitWrap)]
+struct Pat {
+    #[bitfield(16)]
+    program_number: u16,
+    #[bitfield(3, name = _reserved, value = 0b111)]
+    #[bitfield(13)]
+    pid: u16,
+}
+
+
+#[derive(Default, Debug, BitWrap)]
+struct Section<T> {
+    #[bitfield(8)]
+    table_id: u8,
+    #[bitfield]
+    payload: T,
+}
+
+
+#[test]
+fn test_generic() {
+    const DATA: &[u8] = &[0x00, 0x00, 0x01, 0xE1, 0x00];
+
+    let mut section: Section<Pat> = Section::default();
+    let result = section.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(section.payload, Pat { program_number: 1, pid: 0x0100 });
+
+    let mut buffer: [u8; 5] = [0; 5];
+    let result = section.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+
+    let mut section: Section<Vec<u8>> = Section::default();
+    let result = section.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(section.payload, &DATA[1 ..]);
+}
+
+
+#[test]
+fn test_generic_bits() {
+    #[derive(Debug, Default, PartialEq, Clone, Copy, BitWrap)]
+    enum Coffee {
+        #[default]
+        Water,
+        Latte,
+    }
+
+    #[derive(Default, Debug, BitWrap)]
+    struct Packet<'a, T> {
+        #[bitfield(4, name = _reserved, value = 0)]
+        #[bitfield(4)]
+        value: T,
+        marker: PhantomData<&'a ()>,
+    }
+
+    const DATA: &[u8] = &[0x01];
+
+    let mut packet: Packet<Coffee> = Packet::default();
+    packet.unpack(DATA).unwrap();
+
+    assert_eq!(packet.value, Coffee::Latte);
+
+    let mut packet: Packet<u8> = Packet::default();
+    packet.unpack(DATA).unwrap();
+
+    assert_eq!(packet.value, 1);
+
+    let mut buffer: [u8; 1] = [0; 1];
+    packet.pack(&mut buffer).unwrap();
+
+    assert_eq!(buffer, DATA);
+}
+
+
+#[test]
+fn test_const_generic() {
+    #[derive(Debug, BitWrap)]
+    struct Block<const N: usize> {
+        #[bitfield(8)]
+        kind: u8,
+        #[bitfield]
+        data: [u8; N],
+    }
+
+    const DATA: &[u8] = &[0x01, 0x02, 0x03, 0x04];
+
+    let mut block = Block::<3> { kind: 0, data: [0; 3] };
+    let result = block.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(block.kind, 1);
+    assert_eq!(block.data, [0x02, 0x03, 0x04]);
+
+    let mut buffer: [u8; 4] = [0; 4];
+    let result = block.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+}
+
+
+#[test]
+fn test_generic_tag() {
+    #[derive(Default, Debug, PartialEq, BitWrap)]
+    enum Body<T> {
+        #[default]
+        #[bitwrap(tag = 1)]
+        Empty,
+        #[bitwrap(tag = 2)]
+        Payload(T),
+    }
+
+    #[derive(Default, Debug, BitWrap)]
+    struct Message<T> {
+        #[bitfield(8, name = kind)]
+        #[bitfield(tag = kind)]
+        body: Body<T>,
+    }
+
+    const DATA: &[u8] = &[0x02, 0x00, 0x01, 0xE1, 0x00];
+
+    let mut message: Message<Pat> = Message::default();
+    let result = message.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(message.body, Body::Payload(Pat { program_number: 1, pid: 0x0100 }));
+
+    let mut buffer: [u8; 5] = [0; 5];
+    let result = message.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+}
@@ -0,0 +1,173 @@
+use bitwrap::*;
+
+mod common;
+
+
+#[derive(Debug, PartialEq, BitWrap)]
+struct Tlv {
+    #[bitfield(8)] kind: u8,
+    #[bitfield(8, name = len, length_of = data)]
+    #[bitfield]
+    data: Vec<u8>,
+}
+
+
+#[derive(Debug, PartialEq, BitWrap)]
+struct Section {
+    #[bitfield(8)] table_id: u8,
+    #[bitfield(4, name = _reserved, value = 0xB)]
+    #[bitfield(12, name = section_length, length_of = (program, crc))]
+    #[bitfield(16)] program: u16,
+    #[bitfield(8, name = items_len, value = self.items.packed_len())]
+    #[bitfield(items_len)]
+    items: Vec<Tlv>,
+    #[bitfield(32)] crc: u32,
+}
+
+
+#[test]
+fn test_length_of() {
+    let section = Section {
+        table_id: 0x02,
+        program: 0x0001,
+        items: vec![
+            Tlv { kind: 0x0A, data: vec![1, 2, 3] },
+            Tlv { kind: 0x0B, data: vec![] },
+        ],
+        crc: 0x12345678,
+    };
+
+    const DATA: &[u8] = &[
+        0x02, 0xB0, 0x0E,
+        0x00, 0x01,
+        0x07, 0x0A, 0x03, 0x01, 0x02, 0x03, 0x0B, 0x00,
+        0x12, 0x34, 0x56, 0x78,
+    ];
+
+    let data = section.pack_to_vec().unwrap();
+    assert_eq!(data, DATA);
+
+    assert_eq!(Section::unpack_from(DATA), Ok((section, DATA.len())));
+
+    common::unpack_random::<Section>(&[DATA]);
+}
+
+
+#[test]
+fn test_length_of_limit() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Packet {
+        #[bitfield(8, name = len, length_of = (data, data))]
+        #[bitfield]
+        data: Vec<u8>,
+        #[bitfield]
+        tail: Vec<u8>,
+    }
+
+    // nested field gets only covered bytes
+    let (packet, result) = Packet::unpack_from(&[0x02, 0x01, 0x02, 0x03]).unwrap();
+    assert_eq!(result, 4);
+    assert_eq!(packet.data, vec![1, 2]);
+    assert_eq!(packet.tail, vec![3]);
+
+    common::unpack_random::<Packet>(&[&[0x02, 0x01, 0x02, 0x03]]);
+
+    let error = Packet::unpack_from(&[0x05, 0x01, 0x02]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::BufferTooShort { needed: 6 });
+    assert_eq!(error.field_name(), Some("data"));
+}
+
+
+#[test]
+fn test_length_of_errors() {
+    // fields use less bytes than length
+    let error = Section::unpack_from(&[
+        0x02, 0xB0, 0x08,
+        0x00, 0x01,
+        0x00,
+        0x12, 0x34, 0x56, 0x78,
+        0xFF,
+    ]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::TrailingData);
+    assert_eq!(error.field_name(), Some("section_length"));
+    assert_eq!(error.byte_offset(), 10);
+
+    // length does not fit into the field
+    #[derive(Debug, BitWrap)]
+    struct Short {
+        #[bitfield(4, name = _reserved, value = 0)]
+        #[bitfield(4, name = len, length_of = data)]
+        #[bitfield]
+        data: Vec<u8>,
+    }
+
+    let error = Short { data: vec![0; 16] }.pack_to_vec().unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.field_name(), Some("len"));
+    assert_eq!(error.bit_offset(), 4);
+
+    let data = Short { data: vec![0; 15] }.pack_to_vec().unwrap();
+    assert_eq!(data[0], 0x0F);
+}
+
+
+#[test]
+fn test_length_of_nested() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Section {
+        #[bitfield(8)] table_id: u8,
+        #[bitfield(4, name = _reserved, value = 0xB)]
+        #[bitfield(12, name = section_length, length_of = (program, crc))]
+        #[bitfield(16)] program: u16,
+        #[bitfield(4, name = _reserved, value = 0xF)]
+        #[bitfield(12, name = items_length, length_of = items)]
+        #[bitfield]
+        items: Vec<Tlv>,
+        #[bitfield(32)] crc: u32,
+    }
+
+    let section = Section {
+        table_id: 0x02,
+        program: 0x0001,
+        items: vec![Tlv { kind: 0x0A, data: vec![1, 2] }],
+        crc: 0x12345678,
+    };
+
+    const DATA: &[u8] = &[
+        0x02, 0xB0, 0x0C,
+        0x00, 0x01,
+        0xF0, 0x04, 0x0A, 0x02, 0x01, 0x02,
+        0x12, 0x34, 0x56, 0x78,
+    ];
+
+    assert_eq!(section.pack_to_vec().unwrap(), DATA);
+    assert_eq!(Section::unpack_from(DATA), Ok((section, DATA.len())));
+
+    common::unpack_random::<Section>(&[DATA]);
+}
+
+
+#[test]
+fn test_length_of_same_start() {
+    // inner length is declared before the outer length
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Packet {
+        #[bitfield(8, name = data_len, length_of = data)]
+        #[bitfield(8, name = total_len, length_of = (data, tail))]
+        #[bitfield]
+        data: Vec<u8>,
+        #[bitfield(8)] tail: u8,
+    }
+
+    let packet = Packet {
+        data: vec![1, 2],
+        tail: 3,
+    };
+
+    const DATA: &[u8] = &[0x02, 0x03, 0x01, 0x02, 0x03];
+
+    assert_eq!(packet.pack_to_vec().unwrap(), DATA);
+    assert_eq!(Packet::unpack_from(DATA), Ok((packet, DATA.len())));
+
+    common::unpack_random::<Packet>(&[DATA]);
+}
@@ -3,6 +3,8 @@ use {
     bitwrap::*,
 };
 
+mod common;
+
 
 #[test]
 fn test_readme_nested() {
@@ -20,16 +22,9 @@ fn test_readme_nested() {
         0xC0, 0xA8, 0xC8, 0xB7,
     ];
 
-    let mut packet = IP4 {
-        ttl: 0,
-        protocol: 0,
-        checksum: 0,
-        src: Ipv4Addr::new(0, 0, 0, 0),
-        dst: Ipv4Addr::new(0, 0, 0, 0),
-    };
-
-    packet.unpack(DATA).unwrap();
+    let (packet, result) = IP4::unpack_from(DATA).unwrap();
 
+    assert_eq!(result, DATA.len());
     assert_eq!(packet.ttl, 64);
     assert_eq!(packet.protocol, 136);
     assert_eq!(packet.checksum, 0x375D);
@@ -41,4 +36,65 @@ fn test_readme_nested() {
     let result = packet.pack(&mut buffer).unwrap();
 
     assert_eq!(&buffer[.. result], DATA);
+
+    common::unpack_random::<IP4>(&[DATA]);
+}
+
+
+#[test]
+fn test_unpack_from_vec() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Route {
+        #[bitfield(32)] addr: Ipv4Addr,
+        #[bitfield(8)] prefix: u8,
+    }
+
+    #[derive(Debug, BitWrap)]
+    struct Table {
+        #[bitfield(8, name = routes_len, value = self.routes.len() * 5)]
+        #[bitfield(routes_len)]
+        routes: Vec<Route>,
+        // field without bitfield is default
+        counter: usize,
+    }
+
+    const DATA: &[u8] = &[
+        0x0A,
+        0xC0, 0xA8, 0x00, 0x00, 0x10,
+        0x0A, 0x00, 0x00, 0x00, 0x08,
+    ];
+
+    let (table, result) = Table::unpack_from(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(table.counter, 0);
+    assert_eq!(table.routes, vec![
+        Route { addr: Ipv4Addr::new(192, 168, 0, 0), prefix: 16 },
+        Route { addr: Ipv4Addr::new(10, 0, 0, 0), prefix: 8 },
+    ]);
+
+    let mut buffer: Vec<u8> = vec![0; 32];
+    let result = table.pack(&mut buffer).unwrap();
+
+    assert_eq!(&buffer[.. result], DATA);
+
+    common::unpack_random::<Table>(&[DATA]);
+}
+
+
+#[test]
+fn test_unpack_keeps_other_fields() {
+    #[derive(Default, Debug, BitWrap)]
+    struct Packet {
+        #[bitfield(8, name = data_len, value = self.data.len())]
+        #[bitfield(data_len)]
+        data: Vec<u8>,
+        counter: usize,
+    }
+
+    let mut packet = Packet { data: vec![1, 2], counter: 5 };
+    assert_eq!(packet.unpack(&[0x01, 0x03]), Ok(2));
+
+    assert_eq!(packet.data, vec![3]);
+    assert_eq!(packet.counter, 5);
 }
@@ -0,0 +1,161 @@
+use bitwrap::*;
+
+mod common;
+
+
+#[derive(Debug, PartialEq, BitWrap)]
+struct Pid {
+    #[bitfield(3, name = _reserved, value = 0b111)]
+    #[bitfield(13)]
+    pid: u16,
+}
+
+
+#[derive(Debug, PartialEq, BitWrap)]
+#[bitwrap(bits)]
+struct Flags {
+    #[bitfield(1)]
+    a: bool,
+    #[bitfield(2)]
+    b: u8,
+}
+
+
+#[test]
+fn test_nested_bits() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Program {
+        #[bitfield(4)]
+        kind: u8,
+        #[bitfield(16, nested)]
+        pid: Pid,
+        #[bitfield(4)]
+        flags: u8,
+    }
+
+    let program = Program {
+        kind: 0x0A,
+        pid: Pid { pid: 0x1234 },
+        flags: 0x05,
+    };
+
+    const DATA: &[u8] = &[0xAF, 0x23, 0x45];
+
+    assert_eq!(Program::BITS, 24);
+    assert_eq!(program.pack_to_vec().unwrap(), DATA);
+    assert_eq!(Program::unpack_from(DATA), Ok((program, DATA.len())));
+
+    // nested struct is aligned
+    assert_eq!(Pid { pid: 0x1234 }.pack_to_vec().unwrap(), &[0xF2, 0x34]);
+
+    common::unpack_random::<Program>(&[DATA]);
+    common::unpack_bits_random::<Pid>(&[&[0xF2, 0x34]]);
+}
+
+
+#[test]
+fn test_nested_bits_sub_byte() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Packet {
+        #[bitfield(3, nested)]
+        flags: Flags,
+        #[bitfield(5)]
+        value: u8,
+        #[bitfield(5, name = _reserved, value = 0)]
+        #[bitfield(3, nested)]
+        tail: Flags,
+    }
+
+    let packet = Packet {
+        flags: Flags { a: true, b: 2 },
+        value: 0x15,
+        tail: Flags { a: false, b: 3 },
+    };
+
+    const DATA: &[u8] = &[0xD5, 0x03];
+
+    assert_eq!(Flags::BITS, 3);
+    assert_eq!(packet.pack_to_vec().unwrap(), DATA);
+    assert_eq!(Packet::unpack_from(DATA), Ok((packet, DATA.len())));
+
+    common::unpack_random::<Packet>(&[DATA]);
+    common::unpack_bits_random::<Flags>(&[DATA]);
+}
+
+
+#[test]
+fn test_nested_bits_lsb0() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    #[bitwrap(bit_order = lsb0, bits)]
+    struct Flags {
+        #[bitfield(1)]
+        a: bool,
+        #[bitfield(2)]
+        b: u8,
+    }
+
+    #[derive(Debug, PartialEq, BitWrap)]
+    #[bitwrap(bit_order = lsb0)]
+    struct Packet {
+        #[bitfield(3, nested)]
+        flags: Flags,
+        #[bitfield(5)]
+        value: u8,
+    }
+
+    #[derive(Debug, PartialEq, BitWrap)]
+    #[bitwrap(bit_order = lsb0)]
+    struct Flat {
+        #[bitfield(1)]
+        a: bool,
+        #[bitfield(2)]
+        b: u8,
+        #[bitfield(5)]
+        value: u8,
+    }
+
+    let packet = Packet {
+        flags: Flags { a: true, b: 2 },
+        value: 0x15,
+    };
+
+    let flat = Flat { a: true, b: 2, value: 0x15 };
+    let data = flat.pack_to_vec().unwrap();
+
+    assert_eq!(data, &[0xAD]);
+    assert_eq!(packet.pack_to_vec().unwrap(), data);
+    assert_eq!(Packet::unpack_from(&data), Ok((packet, data.len())));
+}
+
+
+#[test]
+fn test_nested_bits_error() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Program {
+        #[bitfield(4)]
+        kind: u8,
+        #[bitfield(16, nested)]
+        pid: Pid,
+        #[bitfield(4)]
+        flags: u8,
+    }
+
+    // error location points to the innermost field
+    let program = Program {
+        kind: 0,
+        pid: Pid { pid: 0x2000 },
+        flags: 0,
+    };
+
+    let error = program.pack_to_vec().unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.struct_name(), Some("Pid"));
+    assert_eq!(error.field_name(), Some("pid"));
+    assert_eq!(error.bit_offset(), 7);
+
+    let error = Program::unpack_from(&[0xAF, 0x23]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::BufferTooShort { needed: 3 });
+    assert_eq!(error.struct_name(), Some("Program"));
+    assert_eq!(error.field_name(), Some("pid"));
+    assert_eq!(error.bit_offset(), 4);
+}
@@ -7,6 +7,8 @@ use {
     },
 };
 
+mod common;
+
 
 #[test]
 fn test_readme_no_std() {
@@ -33,4 +35,6 @@ fn test_readme_no_std() {
 
     assert_eq!(result, DATA.len());
     assert_eq!(buffer, DATA);
+
+    common::unpack_random::<Packet>(&[DATA]);
 }
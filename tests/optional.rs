@@ -0,0 +1,228 @@
+use bitwrap::*;
+
+mod common;
+
+
+#[derive(Debug, PartialEq, BitWrap)]
+struct Header {
+    #[bitfield(2)]
+    pts_dts_flags: u8,
+    #[bitfield(6, name = _reserved, value = 0)]
+    #[bitfield(7, name = _pts_reserved, value = 0, if = pts_dts_flags & 0b10 != 0)]
+    #[bitfield(33, if = pts_dts_flags & 0b10 != 0)]
+    pts: Option<u64>,
+    #[bitfield(7, name = _dts_reserved, value = 0, if = pts_dts_flags == 0b11)]
+    #[bitfield(33, if = pts_dts_flags == 0b11)]
+    dts: Option<u64>,
+    #[bitfield(8)]
+    tail: u8,
+}
+
+
+#[test]
+fn test_optional() {
+    let header = Header {
+        pts_dts_flags: 0b10,
+        pts: Some(0x1_2345_6789),
+        dts: None,
+        tail: 0xFF,
+    };
+
+    const DATA: &[u8] = &[0x80, 0x01, 0x23, 0x45, 0x67, 0x89, 0xFF];
+
+    assert_eq!(header.packed_len(), DATA.len());
+    assert_eq!(header.pack_to_vec().unwrap(), DATA);
+    assert_eq!(Header::unpack_from(DATA), Ok((header, DATA.len())));
+
+    let header = Header {
+        pts_dts_flags: 0b11,
+        pts: Some(1),
+        dts: Some(2),
+        tail: 0xFF,
+    };
+
+    const DATA_DTS: &[u8] = &[
+        0xC0,
+        0x00, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x02,
+        0xFF,
+    ];
+
+    assert_eq!(header.packed_len(), DATA_DTS.len());
+    assert_eq!(header.pack_to_vec().unwrap(), DATA_DTS);
+    assert_eq!(Header::unpack_from(DATA_DTS), Ok((header, DATA_DTS.len())));
+
+    common::unpack_random::<Header>(&[DATA, DATA_DTS]);
+}
+
+
+#[test]
+fn test_optional_none() {
+    let header = Header {
+        pts_dts_flags: 0,
+        pts: None,
+        dts: None,
+        tail: 0xFF,
+    };
+
+    const DATA: &[u8] = &[0x00, 0xFF];
+
+    assert_eq!(header.packed_len(), DATA.len());
+    assert_eq!(header.pack_to_vec().unwrap(), DATA);
+    assert_eq!(Header::unpack_from(DATA), Ok((header, DATA.len())));
+}
+
+
+#[test]
+fn test_optional_mismatch() {
+    // flag is set but value is not defined
+    let header = Header {
+        pts_dts_flags: 0b10,
+        pts: None,
+        dts: None,
+        tail: 0,
+    };
+
+    let error = header.pack_to_vec().unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::Constraint);
+    assert_eq!(error.field_name(), Some("pts"));
+    assert_eq!(error.byte_offset(), 1);
+
+    // value is defined but flag is not set
+    let header = Header {
+        pts_dts_flags: 0b10,
+        pts: Some(1),
+        dts: Some(2),
+        tail: 0,
+    };
+
+    let error = header.pack_to_vec().unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::Constraint);
+    assert_eq!(error.field_name(), Some("dts"));
+    assert_eq!(error.byte_offset(), 6);
+
+    // value is absent in the buffer
+    let error = Header::unpack_from(&[0x80]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::BufferTooShort { needed: 2 });
+    assert_eq!(error.field_name(), Some("_pts_reserved"));
+}
+
+
+#[test]
+fn test_optional_virtual() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Packet {
+        #[bitfield(1, name = has_data, value = self.data.is_some())]
+        #[bitfield(7, name = _reserved, value = 0)]
+        #[bitfield(8, name = data_len, value = self.data.as_ref().map_or(0, Vec::len), if = has_data == 1)]
+        #[bitfield(data_len, if = has_data == 1)]
+        data: Option<Vec<u8>>,
+        #[bitfield(8)]
+        tail: u8,
+    }
+
+    let packet = Packet {
+        data: Some(vec![0xAA, 0xBB]),
+        tail: 0xFF,
+    };
+
+    const DATA: &[u8] = &[0x80, 0x02, 0xAA, 0xBB, 0xFF];
+
+    assert_eq!(packet.packed_len(), DATA.len());
+    assert_eq!(packet.pack_to_vec().unwrap(), DATA);
+    assert_eq!(Packet::unpack_from(DATA), Ok((packet, DATA.len())));
+
+    let packet = Packet {
+        data: None,
+        tail: 0xFF,
+    };
+
+    const DATA_NONE: &[u8] = &[0x00, 0xFF];
+
+    assert_eq!(packet.packed_len(), DATA_NONE.len());
+    assert_eq!(packet.pack_to_vec().unwrap(), DATA_NONE);
+    assert_eq!(Packet::unpack_from(DATA_NONE), Ok((packet, DATA_NONE.len())));
+
+    common::unpack_random::<Packet>(&[DATA, DATA_NONE]);
+}
+
+
+#[test]
+fn test_optional_group() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Extension {
+        #[bitfield(8)]
+        flags: u8,
+        #[bitfield(4, if = flags & 0x01 != 0)]
+        kind: Option<u8>,
+        #[bitfield(12, if = flags & 0x01 != 0)]
+        id: Option<u16>,
+    }
+
+    let extension = Extension {
+        flags: 0x01,
+        kind: Some(0x0A),
+        id: Some(0x123),
+    };
+
+    const DATA: &[u8] = &[0x01, 0xA1, 0x23];
+
+    assert_eq!(extension.packed_len(), DATA.len());
+    assert_eq!(extension.pack_to_vec().unwrap(), DATA);
+    assert_eq!(Extension::unpack_from(DATA), Ok((extension, DATA.len())));
+
+    let extension = Extension {
+        flags: 0x00,
+        kind: None,
+        id: None,
+    };
+
+    assert_eq!(extension.pack_to_vec().unwrap(), &[0x00]);
+    assert_eq!(Extension::unpack_from(&[0x00]), Ok((extension, 1)));
+
+    common::unpack_random::<Extension>(&[DATA]);
+}
+
+
+#[test]
+fn test_optional_length() {
+    // length of the fields that end with optional field
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Packet {
+        #[bitfield(8)]
+        flags: u8,
+        #[bitfield(8, name = len, length_of = (a, b))]
+        #[bitfield(8)]
+        a: u8,
+        #[bitfield(8, if = flags != 0)]
+        b: Option<u8>,
+        #[bitfield(8)]
+        c: u8,
+    }
+
+    let packet = Packet {
+        flags: 1,
+        a: 2,
+        b: Some(3),
+        c: 4,
+    };
+
+    const DATA: &[u8] = &[0x01, 0x02, 0x02, 0x03, 0x04];
+
+    assert_eq!(packet.pack_to_vec().unwrap(), DATA);
+    assert_eq!(Packet::unpack_from(DATA), Ok((packet, DATA.len())));
+
+    let packet = Packet {
+        flags: 0,
+        a: 2,
+        b: None,
+        c: 4,
+    };
+
+    const DATA_NONE: &[u8] = &[0x00, 0x01, 0x02, 0x04];
+
+    assert_eq!(packet.pack_to_vec().unwrap(), DATA_NONE);
+    assert_eq!(Packet::unpack_from(DATA_NONE), Ok((packet, DATA_NONE.len())));
+
+    common::unpack_random::<Packet>(&[DATA, DATA_NONE]);
+}
@@ -0,0 +1,112 @@
+use bitwrap::*;
+
+
+#[derive(Default, Debug, PartialEq, BitWrap)]
+struct Packet {
+    #[bitfield(4)]
+    version: u8,
+    #[bitfield(4, truncate)]
+    flags: u8,
+    #[bitfield(8, name = data_len, value = self.data.len())]
+    #[bitfield(data_len)]
+    data: Vec<u8>,
+}
+
+
+#[test]
+fn test_overflow_field() {
+    let mut packet = Packet {
+        version: 0x1F,
+        ..Default::default()
+    };
+
+    let mut buffer: [u8; 8] = [0; 8];
+    assert_eq!(
+        packet.pack(&mut buffer),
+        Err(BitWrapError::new(BitWrapErrorKind::InvalidValue).at_field("Packet", "version", 0))
+    );
+
+    packet.version = 0x0F;
+    assert_eq!(packet.pack(&mut buffer), Ok(2));
+}
+
+
+#[test]
+fn test_overflow_truncate() {
+    let packet = Packet {
+        version: 0x01,
+        flags: 0x1F,
+        data: vec![0xAA],
+    };
+
+    let mut buffer: [u8; 3] = [0; 3];
+    let result = packet.pack(&mut buffer).unwrap();
+
+    assert_eq!(&buffer[.. result], &[0x1F, 0x01, 0xAA]);
+}
+
+
+#[test]
+fn test_overflow_virtual() {
+    let packet = Packet {
+        data: vec![0; 300],
+        ..Default::default()
+    };
+
+    let mut buffer = vec![0; 512];
+    assert_eq!(
+        packet.pack(&mut buffer),
+        Err(BitWrapError::new(BitWrapErrorKind::InvalidValue).at_field("Packet", "data_len", 8))
+    );
+
+    #[derive(Default, Debug, BitWrap)]
+    struct Truncated {
+        #[bitfield(8, name = data_len, value = self.data.len(), truncate)]
+        #[bitfield]
+        data: Vec<u8>,
+    }
+
+    let packet = Truncated {
+        data: vec![0; 300],
+    };
+
+    assert_eq!(packet.pack(&mut buffer), Ok(301));
+    assert_eq!(buffer[0], 44);
+}
+
+
+#[test]
+fn test_overflow_signed() {
+    #[derive(Default, Debug, BitWrap)]
+    struct Sample {
+        #[bitfield(4)]
+        a: i8,
+        #[bitfield(4, sign = sign_magnitude)]
+        b: i8,
+        #[bitfield(8, sign = ones_complement)]
+        c: i8,
+    }
+
+    let mut buffer: [u8; 2] = [0; 2];
+    let mut sample = Sample {
+        a: -8,
+        b: -7,
+        c: -127,
+    };
+    assert_eq!(sample.pack(&mut buffer), Ok(2));
+    assert_eq!(buffer, [0x8F, 0x80]);
+
+    sample.a = 8;
+    assert_eq!(sample.pack(&mut buffer).unwrap_err().field_name(), Some("a"));
+
+    sample.a = -9;
+    assert_eq!(sample.pack(&mut buffer).unwrap_err().field_name(), Some("a"));
+
+    sample.a = 0;
+    sample.b = -8;
+    assert_eq!(sample.pack(&mut buffer).unwrap_err().field_name(), Some("b"));
+
+    sample.b = 0;
+    sample.c = -128;
+    assert_eq!(sample.pack(&mut buffer).unwrap_err().field_name(), Some("c"));
+}
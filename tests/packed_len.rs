@@ -0,0 +1,74 @@
+use bitwrap::*;
+
+
+#[derive(Debug, PartialEq, BitWrap)]
+struct Item {
+    #[bitfield(8)] id: u8,
+    #[bitfield(8, name = name_len, value = self.name.len())]
+    #[bitfield(name_len)]
+    name: String,
+}
+
+
+#[derive(Debug, PartialEq, BitWrap)]
+enum Body {
+    #[bitwrap(tag = 0x01)]
+    Item(Item),
+    #[bitwrap(tag = 0xFF)]
+    Stuffing,
+    #[bitwrap(unknown)]
+    Other(u8, Vec<u8>),
+}
+
+
+#[derive(Debug, PartialEq, BitWrap)]
+struct Packet {
+    #[bitfield(4, name = _reserved, value = 0xF)]
+    #[bitfield(12)] pid: u16,
+    #[bitfield] key: [u8; 2],
+    #[bitfield(8, name = body_tag)]
+    #[bitfield(8, name = body_len, value = self.body.packed_len())]
+    #[bitfield(body_len, tag = body_tag)]
+    body: Body,
+    #[bitfield(8, name = items_len, value = self.items.packed_len())]
+    #[bitfield(items_len)]
+    items: Vec<Item>,
+}
+
+
+#[test]
+fn test_packed_len() {
+    let packet = Packet {
+        pid: 0x100,
+        key: [0xAB, 0xCD],
+        body: Body::Other(0x10, vec![1, 2, 3]),
+        items: vec![
+            Item { id: 1, name: "one".to_owned() },
+            Item { id: 2, name: String::new() },
+        ],
+    };
+
+    assert_eq!(packet.items[0].packed_len(), 5);
+    assert_eq!(packet.items.packed_len(), 7);
+    assert_eq!(packet.body.packed_len(), 3);
+    assert_eq!(Body::Stuffing.packed_len(), 0);
+    assert_eq!(packet.packed_len(), 4 + 2 + 3 + 1 + 7);
+
+    let data = packet.pack_to_vec().unwrap();
+    assert_eq!(data, vec![
+        0xF1, 0x00, 0xAB, 0xCD,
+        0x10, 0x03, 0x01, 0x02, 0x03,
+        0x07, 0x01, 0x03, b'o', b'n', b'e', 0x02, 0x00,
+    ]);
+
+    assert_eq!(Packet::unpack_from(&data), Ok((packet, data.len())));
+}
+
+
+#[test]
+fn test_pack_to_vec_error() {
+    let item = Item { id: 1, name: "x".repeat(256) };
+    let error = item.pack_to_vec().unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.field_name(), Some("name_len"));
+}
@@ -1,48 +1,20 @@
 use {
-    core::convert::{
-        TryFrom,
-        Infallible,
-    },
     std::net::Ipv4Addr,
     bitwrap::{
         BitWrap,
         BitWrapExt,
-        BitWrapError,
     },
 };
 
+mod common;
+
 
 #[test]
 fn test_readme() {
-    #[derive(Debug, PartialEq, Clone, Copy)]
+    #[derive(Debug, PartialEq, Clone, Copy, BitWrap)]
     enum Variant {
-        Value55,
-        ValueAA,
-    }
-
-    impl Default for Variant {
-        fn default() -> Self { Variant::Value55 }
-    }
-
-    impl TryFrom<u8> for Variant {
-        type Error = BitWrapError;
-        fn try_from(value: u8) -> Result<Self, Self::Error> {
-            match value {
-                0x55 => Ok(Variant::Value55),
-                0xAA => Ok(Variant::ValueAA),
-                _ => Err(BitWrapError),
-            }
-        }
-    }
-
-    impl TryFrom<Variant> for u8 {
-        type Error = Infallible;
-        fn try_from(value: Variant) -> Result<Self, Self::Error> {
-            match value {
-                Variant::Value55 => Ok(0x55),
-                Variant::ValueAA => Ok(0xAA),
-            }
-        }
+        Value55 = 0x55,
+        ValueAA = 0xAA,
     }
 
     #[derive(BitWrap)]
@@ -108,4 +80,6 @@ fn test_readme() {
 
     assert_eq!(result, DATA.len());
     assert_eq!(&buffer[.. result], DATA);
+
+    common::unpack_random::<Packet>(&[DATA]);
 }
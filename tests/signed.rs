@@ -0,0 +1,111 @@
+use bitwrap::*;
+
+
+#[test]
+fn test_signed() {
+    #[derive(Default, Debug, PartialEq, BitWrap)]
+    struct Packet {
+        #[bitfield(4)]
+        a: i8,
+        #[bitfield(12)]
+        b: i16,
+        #[bitfield(20)]
+        c: i32,
+        #[bitfield(4)]
+        d: i64,
+        #[bitfield(8)]
+        e: i128,
+        #[bitfield(16)]
+        f: i16,
+    }
+
+    const DATA: &[u8] = &[0xDC, 0x18, 0xB6, 0xC2, 0x07, 0x80, 0x7F, 0xFF];
+
+    let mut packet = Packet::default();
+    let result = packet.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(packet, Packet {
+        a: -3,
+        b: -1000,
+        c: -300000,
+        d: 7,
+        e: -128,
+        f: 32767,
+    });
+
+    let mut buffer: [u8; 8] = [0; 8];
+    let result = packet.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+}
+
+
+#[test]
+fn test_signed_encoding() {
+    #[derive(Default, Debug, PartialEq, BitWrap)]
+    struct Packet {
+        #[bitfield(4, sign = sign_magnitude)]
+        a: i8,
+        #[bitfield(4, sign = ones_complement)]
+        b: i8,
+        #[bitfield(12, sign = sign_magnitude)]
+        c: i16,
+        #[bitfield(12, sign = ones_complement)]
+        d: i16,
+        #[bitfield(8, sign = ones_complement)]
+        e: i8,
+        #[bitfield(8, sign = sign_magnitude)]
+        f: i8,
+    }
+
+    const DATA: &[u8] = &[0xBC, 0xBE, 0x83, 0xE8, 0x80, 0x05];
+
+    let mut packet = Packet::default();
+    let result = packet.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(packet, Packet {
+        a: -3,
+        b: -3,
+        c: -1000,
+        d: 1000,
+        e: -127,
+        f: 5,
+    });
+
+    let mut buffer: [u8; 6] = [0; 6];
+    let result = packet.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+}
+
+
+#[test]
+fn test_signed_newtype() {
+    #[derive(Default, Debug, PartialEq, Clone, Copy, BitWrap)]
+    struct Temperature(#[bitfield(12)] i16);
+
+    #[derive(Default, Debug, PartialEq, BitWrap)]
+    struct Sensor {
+        #[bitfield(4, name = _reserved, value = 0xF)]
+        #[bitfield(12)]
+        temperature: Temperature,
+    }
+
+    const DATA: &[u8] = &[0xFF, 0xD8];
+
+    let mut sensor = Sensor::default();
+    let result = sensor.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(sensor.temperature, Temperature(-40));
+
+    let mut buffer: [u8; 2] = [0; 2];
+    let result = sensor.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+}
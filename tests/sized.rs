@@ -0,0 +1,40 @@
+use bitwrap::*;
+
+
+#[test]
+fn test_sized() {
+    #[derive(Default, Debug, BitWrap)]
+    #[bitwrap(size = 4)]
+    struct Header {
+        #[bitfield(8, name = _sync, value = 0x47)]
+        #[bitfield(1)] error: bool,
+        #[bitfield(2, name = _reserved, value = 0)]
+        #[bitfield(13)] pid: u16,
+        #[bitfield(8)] counter: u8,
+    }
+
+    #[derive(Debug, BitWrap)]
+    struct Packet {
+        #[bitfield] header: [u8; 4],
+        #[bitfield] payload: [u8; 184],
+    }
+
+    assert_eq!(Header::BITS, 32);
+    assert_eq!(Header::SIZE, 4);
+    assert_eq!(Packet::SIZE, 188);
+
+    let mut buffer = [0; Header::SIZE];
+    let header = Header { pid: 0x100, ..Default::default() };
+    assert_eq!(header.pack(&mut buffer), Ok(Header::SIZE));
+    assert_eq!(buffer, [0x47, 0x01, 0x00, 0x00]);
+}
+
+
+#[test]
+fn test_sized_newtype() {
+    #[derive(Default, Debug, BitWrap)]
+    struct Pid(#[bitfield(16)] u16);
+
+    assert_eq!(Pid::BITS, 16);
+    assert_eq!(Pid::SIZE, 2);
+}
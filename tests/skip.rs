@@ -1,5 +1,7 @@
 use bitwrap::*;
 
+mod common;
+
 
 #[test]
 fn test_readme_skip() {
@@ -25,4 +27,6 @@ fn test_readme_skip() {
 
     assert_eq!(result, DATA.len());
     assert_eq!(&buffer[.. result], DATA);
+
+    common::unpack_random::<Packet>(&[DATA]);
 }
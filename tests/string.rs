@@ -1,5 +1,7 @@
 use bitwrap::*;
 
+mod common;
+
 
 #[test]
 fn test_string() {
@@ -23,4 +25,6 @@ fn test_string() {
 
     assert_eq!(result, DATA.len());
     assert_eq!(&buffer[.. result], DATA);
+
+    common::unpack_random::<Packet>(&[DATA]);
 }
@@ -0,0 +1,202 @@
+use bitwrap::*;
+
+mod common;
+
+
+#[derive(Default, Debug, PartialEq, BitWrap)]
+struct Language {
+    #[bitfield]
+    code: [u8; 3],
+    #[bitfield(8)]
+    audio_type: u8,
+}
+
+
+#[derive(Default, Debug, PartialEq, BitWrap)]
+struct Service {
+    #[bitfield(8)]
+    service_type: u8,
+    #[bitfield(8, name = name_len, value = self.name.len())]
+    #[bitfield(name_len)]
+    name: Vec<u8>,
+}
+
+
+#[derive(Default, Debug, PartialEq, BitWrap)]
+enum DescriptorBody {
+    #[bitwrap(tag = 0x0A)]
+    Language(Language),
+    #[bitwrap(tag = 0x48)]
+    Service(Service),
+    #[default]
+    #[bitwrap(tag = 0xFF)]
+    Stuffing,
+    #[bitwrap(unknown)]
+    Other(u8, Vec<u8>),
+}
+
+
+#[derive(Default, Debug, BitWrap)]
+struct Descriptor {
+    #[bitfield(8, name = descriptor_tag)]
+    #[bitfield(tag = descriptor_tag)]
+    body: DescriptorBody,
+}
+
+
+#[test]
+fn test_tag() {
+    const DATA: &[u8] = &[0x0A, b'e', b'n', b'g', 0x01];
+
+    let mut descriptor = Descriptor::default();
+    let result = descriptor.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(descriptor.body, DescriptorBody::Language(Language {
+        code: *b"eng",
+        audio_type: 1,
+    }));
+
+    let mut buffer: [u8; 16] = [0; 16];
+    let result = descriptor.pack(&mut buffer).unwrap();
+
+    assert_eq!(&buffer[.. result], DATA);
+
+    descriptor.body = DescriptorBody::Service(Service {
+        service_type: 0x19,
+        name: b"TV".to_vec(),
+    });
+    let result = descriptor.pack(&mut buffer).unwrap();
+
+    assert_eq!(&buffer[.. result], &[0x48, 0x19, 0x02, b'T', b'V']);
+
+    common::unpack_random::<Descriptor>(&[
+        DATA,
+        &[0x48, 0x19, 0x02, b'T', b'V'],
+        &[0x99, 0x01, 0x02, 0x03],
+    ]);
+}
+
+
+#[test]
+fn test_tag_unit() {
+    const DATA: &[u8] = &[0xFF];
+
+    let mut descriptor = Descriptor {
+        body: DescriptorBody::Language(Language::default()),
+    };
+    let result = descriptor.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(descriptor.body, DescriptorBody::Stuffing);
+
+    let mut buffer: [u8; 1] = [0; 1];
+    let result = descriptor.pack(&mut buffer).unwrap();
+
+    assert_eq!(&buffer[.. result], DATA);
+}
+
+
+#[test]
+fn test_tag_unknown() {
+    const DATA: &[u8] = &[0x99, 0x01, 0x02, 0x03];
+
+    let mut descriptor = Descriptor::default();
+    let result = descriptor.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(descriptor.body, DescriptorBody::Other(0x99, vec![0x01, 0x02, 0x03]));
+
+    let mut buffer: [u8; 4] = [0; 4];
+    let result = descriptor.pack(&mut buffer).unwrap();
+
+    assert_eq!(&buffer[.. result], DATA);
+}
+
+
+#[test]
+fn test_tag_field() {
+    #[derive(Default, Debug, PartialEq, BitWrap)]
+    enum Body {
+        #[default]
+        #[bitwrap(tag = 1)]
+        Ping,
+        #[bitwrap(tag = 0x102)]
+        Data(Vec<u8>),
+    }
+
+    #[derive(Default, Debug, BitWrap)]
+    struct Message {
+        #[bitfield(4, name = _version, value = 1)]
+        #[bitfield(12)]
+        kind: u16,
+        #[bitfield(8, name = len, value = self.body.pack(&mut [0; 256])?)]
+        #[bitfield(len, tag = kind)]
+        body: Body,
+    }
+
+    const DATA: &[u8] = &[0x11, 0x02, 0x02, 0xAB, 0xCD, 0xEF];
+
+    let mut message = Message::default();
+    let result = message.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len() - 1);
+    assert_eq!(message.kind, 0x102);
+    assert_eq!(message.body, Body::Data(vec![0xAB, 0xCD]));
+
+    // tag written from the body variant
+    message.kind = 0;
+    let mut buffer: [u8; 8] = [0; 8];
+    let result = message.pack(&mut buffer).unwrap();
+
+    assert_eq!(&buffer[.. result], &DATA[.. 5]);
+
+    message.body = Body::Ping;
+    let result = message.pack(&mut buffer).unwrap();
+
+    assert_eq!(&buffer[.. result], &[0x10, 0x01, 0x00]);
+
+    // unknown tag
+    let error = message.unpack(&[0x10, 0x03, 0x00]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.field_name(), Some("body"));
+    assert_eq!(error.byte_offset(), 3);
+
+    common::unpack_random::<Message>(&[DATA]);
+}
+
+
+#[test]
+fn test_tag_unpack_from() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    enum Body {
+        #[bitwrap(tag = 0x0A)]
+        Language(Language),
+        #[bitwrap(tag = 0xFF)]
+        Stuffing,
+    }
+
+    #[derive(Debug, BitWrap)]
+    struct Descriptor {
+        #[bitfield(8, name = descriptor_tag)]
+        #[bitfield(tag = descriptor_tag)]
+        body: Body,
+    }
+
+    let (descriptor, result) = Descriptor::unpack_from(&[0x0A, b'e', b'n', b'g', 0x01]).unwrap();
+    assert_eq!(result, 5);
+    assert_eq!(descriptor.body, Body::Language(Language {
+        code: *b"eng",
+        audio_type: 1,
+    }));
+
+    let (descriptor, result) = Descriptor::unpack_from(&[0xFF]).unwrap();
+    assert_eq!(result, 1);
+    assert_eq!(descriptor.body, Body::Stuffing);
+
+    let error = Descriptor::unpack_from(&[0x48, 0x00]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.field_name(), Some("body"));
+
+    common::unpack_random::<Descriptor>(&[&[0x0A, b'e', b'n', b'g', 0x01], &[0xFF]]);
+}
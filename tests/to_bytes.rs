@@ -0,0 +1,64 @@
+use bitwrap::*;
+
+
+#[test]
+fn test_to_bytes() {
+    #[derive(Default, Debug, PartialEq, BitWrap)]
+    struct Header {
+        #[bitfield(8, name = _sync, value = 0x47)]
+        #[bitfield(1)] error: bool,
+        #[bitfield(2, name = _reserved, value = 0b11)]
+        #[bitfield(13, truncate)] pid: u16,
+        #[bitfield(4, truncate)] counter: u8,
+        #[bitfield(12, endian = little, truncate)] offset: i16,
+        #[bitfield] key: [u8; 2],
+    }
+
+    const DATA: [u8; 7] = [0x47, 0xE1, 0x00, 0x5F, 0xFF, 0xAB, 0xCD];
+
+    let header = Header {
+        error: true,
+        pid: 0x100,
+        counter: 5,
+        offset: -1,
+        key: [0xAB, 0xCD],
+    };
+
+    assert_eq!(header.to_bytes(), DATA);
+    assert_eq!(Header::from_bytes(&DATA), header);
+
+    // same result as pack and unpack
+    let mut buffer = [0; Header::SIZE];
+    assert_eq!(header.pack(&mut buffer), Ok(Header::SIZE));
+    assert_eq!(buffer, DATA);
+    assert_eq!(Header::unpack_from(&DATA), Ok((header, Header::SIZE)));
+}
+
+
+#[test]
+fn test_to_bytes_truncate() {
+    #[derive(Default, Debug, PartialEq, BitWrap)]
+    struct Register {
+        #[bitfield(4, truncate)] mode: u8,
+        #[bitfield(12, truncate)] value: u32,
+    }
+
+    let register = Register { mode: 0x1F, value: 0x1234 };
+    assert_eq!(register.to_bytes(), [0xF2, 0x34]);
+    assert_eq!(Register::from_bytes(&[0xF2, 0x34]), Register { mode: 0xF, value: 0x234 });
+}
+
+
+#[test]
+fn test_to_bytes_virtual() {
+    #[derive(Default, Debug, PartialEq, BitWrap)]
+    struct Packet {
+        #[bitfield(4, name = _reserved, value = 0xF)]
+        #[bitfield(4, name = mode, value = self.mode, truncate)]
+        #[bitfield(8)] value: u8,
+        mode: u8,
+    }
+
+    let packet = Packet { value: 0x12, mode: 0x1A };
+    assert_eq!(packet.to_bytes(), [0xFA, 0x12]);
+}
@@ -0,0 +1,83 @@
+use {
+    core::convert::TryFrom,
+    bitwrap::*,
+};
+
+
+#[derive(Default, Debug, PartialEq, Clone, Copy, BitWrap)]
+struct Pid(#[bitfield(13)] u16);
+
+
+#[test]
+fn test_tuple() {
+    #[derive(Default, Debug, BitWrap)]
+    struct Header(
+        #[bitfield(1)] bool,
+        #[bitfield(2, name = _reserved, value = 0b11)]
+        #[bitfield(13)] u16,
+        #[bitfield(8, name = data_len, value = self.2.len())]
+        #[bitfield(data_len)] Vec<u8>,
+    );
+
+    const DATA: &[u8] = &[0xF2, 0x34, 0x02, 0xAB, 0xCD];
+
+    let mut header = Header::default();
+    let result = header.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert!(header.0);
+    assert_eq!(header.1, 0x1234);
+    assert_eq!(header.2, vec![0xAB, 0xCD]);
+
+    let mut buffer: [u8; 5] = [0; 5];
+    let result = header.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+}
+
+
+#[test]
+fn test_newtype() {
+    #[derive(Default, Debug, BitWrap)]
+    struct Packet {
+        #[bitfield(3, name = _reserved, value = 0b111)]
+        #[bitfield(13)] pid: Pid,
+    }
+
+    const DATA: &[u8] = &[0xE0, 0x11];
+
+    let mut packet = Packet::default();
+    let result = packet.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(packet.pid, Pid(0x0011));
+
+    let mut buffer: [u8; 2] = [0; 2];
+    let result = packet.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+}
+
+
+#[test]
+fn test_newtype_aligned() {
+    #[derive(Default, Debug, PartialEq, Clone, Copy, BitWrap)]
+    struct Version(#[bitfield(8)] u8);
+
+    const DATA: &[u8] = &[0x02];
+
+    let mut version = Version::default();
+    let result = version.unpack(DATA).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(version, Version(2));
+    assert_eq!(u8::try_from(version), Ok(2));
+
+    let mut buffer: [u8; 1] = [0; 1];
+    let result = version.pack(&mut buffer).unwrap();
+
+    assert_eq!(result, DATA.len());
+    assert_eq!(buffer, DATA);
+}
@@ -0,0 +1,50 @@
+use bitwrap::*;
+
+
+#[derive(Default, BitWrap)]
+struct Nested {
+    #[bitfield(8)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+struct NestedNotAligned {
+    #[bitfield(4, name = _reserved, value = 0)]
+    #[bitfield]
+    nested: Nested,
+}
+
+
+#[derive(BitWrap)]
+struct ArrayNotAligned {
+    #[bitfield(12)]
+    version: u16,
+    #[bitfield]
+    data: [u8; 4],
+}
+
+
+#[derive(BitWrap)]
+struct LengthNotAligned {
+    #[bitfield(8)]
+    len: u8,
+    #[bitfield(len)]
+    data: Vec<u8>,
+    #[bitfield(1)]
+    flag: bool,
+    #[bitfield(len)]
+    tail: Vec<u8>,
+}
+
+
+#[derive(BitWrap)]
+struct StructNotAligned {
+    #[bitfield]
+    data: [u8; 2],
+    #[bitfield(13)]
+    pid: u16,
+}
+
+
+fn main() {}
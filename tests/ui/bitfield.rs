@@ -0,0 +1,161 @@
+use bitwrap::*;
+
+
+#[derive(BitWrap)]
+struct ZeroSize {
+    #[bitfield(0)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+struct LargeSize {
+    #[bitfield(129)]
+    value: u128,
+}
+
+
+#[derive(BitWrap)]
+struct UnexpectedOption {
+    #[bitfield(8, size = 1)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+struct EmptyOption {
+    #[bitfield(8, name = _reserved, value = )]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+struct MissingComma {
+    #[bitfield(8 name = _reserved)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+struct ValueWithoutName {
+    #[bitfield(8, value = 1)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+struct NameWithoutValue {
+    #[bitfield(8, name = _reserved)]
+    #[bitfield(8)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+struct NameWithoutBits {
+    #[bitfield(name = _reserved, value = 0)]
+    #[bitfield(8)]
+    value: u8,
+}
+
+
+#[derive(Default, BitWrap)]
+enum Body {
+    #[default]
+    #[bitwrap(tag = 1)]
+    Ping,
+}
+
+
+#[derive(BitWrap)]
+struct TagWithBits {
+    #[bitfield(8, name = kind)]
+    #[bitfield(8, tag = kind)]
+    body: Body,
+}
+
+
+#[derive(BitWrap)]
+struct TagNotIdent {
+    #[bitfield(8, name = kind)]
+    #[bitfield(tag = 1)]
+    body: Body,
+}
+
+
+#[derive(BitWrap)]
+struct WrongEndian {
+    #[bitfield(16, endian = middle)]
+    value: u16,
+}
+
+
+#[derive(BitWrap)]
+#[bitwrap(order = little)]
+struct UnexpectedStructOption {
+    #[bitfield(8)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+#[bitwrap(bit_order = lsb)]
+struct WrongBitOrder {
+    #[bitfield(8)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+struct SignedOverflow {
+    #[bitfield(12)]
+    value: i8,
+}
+
+
+#[derive(BitWrap)]
+struct SignUnsigned {
+    #[bitfield(8, sign = sign_magnitude)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+struct WrongSign {
+    #[bitfield(8, sign = unsigned)]
+    value: i8,
+}
+
+
+#[derive(BitWrap)]
+struct TruncateWithoutBits {
+    #[bitfield(8, name = data_len, value = self.data.len())]
+    #[bitfield(data_len, truncate)]
+    data: Vec<u8>,
+}
+
+
+#[derive(BitWrap)]
+struct CheckWithoutValue {
+    #[bitfield(8, check)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+struct CountWithBits {
+    #[bitfield(8, name = n_items)]
+    #[bitfield(8, count = n_items)]
+    items: Vec<u8>,
+}
+
+
+#[derive(BitWrap)]
+struct CountNotName {
+    #[bitfield(8, name = n_items)]
+    #[bitfield(count = 2)]
+    items: Vec<u8>,
+}
+
+
+fn main() {}
@@ -0,0 +1,16 @@
+use bitwrap::*;
+
+
+struct Context;
+
+
+// unpack_from builds struct with default value of the field without bitfield
+#[derive(BitWrap)]
+struct Packet {
+    #[bitfield(8)]
+    value: u8,
+    context: Context,
+}
+
+
+fn main() {}
@@ -0,0 +1,118 @@
+use bitwrap::*;
+
+
+#[derive(BitWrap)]
+union Value {
+    byte: u8,
+    word: u16,
+}
+
+
+const BASE: u8 = 1;
+
+
+#[derive(BitWrap)]
+enum NotLiteral {
+    First = BASE as isize,
+}
+
+
+#[derive(BitWrap)]
+enum WithFields {
+    First,
+    Second(u8),
+}
+
+
+#[derive(BitWrap)]
+enum NamedFields {
+    #[bitwrap(tag = 1)]
+    First { value: u8 },
+}
+
+
+#[derive(BitWrap)]
+enum UnknownFields {
+    First,
+    #[bitwrap(unknown)]
+    Other(u8, u8),
+}
+
+
+#[derive(BitWrap)]
+enum UnknownTwice {
+    #[bitwrap(unknown)]
+    Other(u8),
+    #[bitwrap(unknown)]
+    Another(u8),
+}
+
+
+#[derive(BitWrap)]
+enum TagUnknownFields {
+    #[bitwrap(tag = 1)]
+    First,
+    #[bitwrap(unknown)]
+    Other(u8),
+}
+
+
+#[derive(BitWrap)]
+enum TagMissing {
+    #[bitwrap(tag = 1)]
+    First,
+    Second,
+}
+
+
+#[derive(BitWrap)]
+enum TagFields {
+    #[bitwrap(tag = 1)]
+    First(u8, u8),
+}
+
+
+#[derive(BitWrap)]
+enum TagDuplicate {
+    #[bitwrap(tag = 1)]
+    First(u8),
+    #[bitwrap(tag = 0x01)]
+    Second(u8),
+}
+
+
+#[derive(BitWrap)]
+enum TagNotInteger {
+    #[bitwrap(tag = "1")]
+    First,
+}
+
+
+#[derive(BitWrap)]
+enum UnexpectedOption {
+    #[bitwrap(default)]
+    First,
+}
+
+
+#[derive(BitWrap)]
+enum WrongFormat {
+    #[bitwrap = 1]
+    First,
+}
+
+
+#[derive(BitWrap)]
+#[bitwrap(endian = little)]
+enum EnumEndian {
+    First,
+}
+
+
+#[derive(BitWrap)]
+enum DiscriminantOverflow {
+    First = 340282366920938463463374607431768211456,
+}
+
+
+fn main() {}
@@ -0,0 +1,103 @@
+use bitwrap::*;
+
+
+#[derive(BitWrap)]
+struct LengthWithoutName {
+    #[bitfield(8, length_of = data)]
+    #[bitfield]
+    data: Vec<u8>,
+}
+
+
+#[derive(BitWrap)]
+struct LengthWithValue {
+    #[bitfield(8, name = len, value = 1, length_of = data)]
+    #[bitfield]
+    data: Vec<u8>,
+}
+
+
+#[derive(BitWrap)]
+struct LengthBeforeField {
+    #[bitfield]
+    data: Vec<u8>,
+    #[bitfield(8, name = len, length_of = data)]
+    #[bitfield(8)]
+    kind: u8,
+}
+
+
+#[derive(BitWrap)]
+struct LengthNotAligned {
+    #[bitfield(8, name = len, length_of = flag)]
+    #[bitfield(1)]
+    flag: bool,
+    #[bitfield(7)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+struct UnitWithoutLength {
+    #[bitfield(8, unit = words32)]
+    len: u8,
+}
+
+
+#[derive(BitWrap)]
+struct UnitUnknown {
+    #[bitfield(8, name = len, length_of = data, unit = words)]
+    #[bitfield]
+    data: Vec<u8>,
+}
+
+
+#[derive(BitWrap)]
+struct ElementsLengthOf {
+    #[bitfield(8, name = len, length_of = data, unit = elements)]
+    #[bitfield]
+    data: Vec<u8>,
+}
+
+
+#[derive(BitWrap)]
+struct ElementsNotVec {
+    #[bitfield(8, name = len, value = self.data.len())]
+    #[bitfield(len, unit = elements)]
+    data: String,
+}
+
+
+#[derive(BitWrap)]
+struct LengthCross {
+    #[bitfield(8, name = la, length_of = (a, b))]
+    #[bitfield(8, name = lb, length_of = (b, c))]
+    #[bitfield(8)]
+    a: u8,
+    #[bitfield(8)]
+    b: u8,
+    #[bitfield(8)]
+    c: u8,
+    #[bitfield(8)]
+    d: u8,
+}
+
+
+#[derive(BitWrap)]
+struct UnitFieldLength {
+    #[bitfield(8)]
+    len: u8,
+    #[bitfield(self.len, unit = words32)]
+    data: Vec<u8>,
+}
+
+
+#[derive(BitWrap)]
+struct UnitZero {
+    #[bitfield(8, name = len, length_of = data, unit = 0)]
+    #[bitfield]
+    data: Vec<u8>,
+}
+
+
+fn main() {}
@@ -0,0 +1,47 @@
+use bitwrap::*;
+
+
+#[derive(BitWrap)]
+struct Pid {
+    #[bitfield(3, name = _reserved, value = 0b111)]
+    #[bitfield(13)]
+    pid: u16,
+}
+
+
+#[derive(BitWrap)]
+struct NestedWithoutBits {
+    #[bitfield(nested)]
+    pid: Pid,
+}
+
+
+#[derive(BitWrap)]
+struct NestedVirtual {
+    #[bitfield(16, name = pid, value = 0, nested)]
+    #[bitfield(8)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+struct NestedSizeMismatch {
+    #[bitfield(4, name = _reserved, value = 0)]
+    #[bitfield(13, nested)]
+    pid: Pid,
+    #[bitfield(7)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+#[bitwrap(bits)]
+struct BitsWithLength {
+    #[bitfield(8)]
+    len: u8,
+    #[bitfield(len)]
+    data: Vec<u8>,
+}
+
+
+fn main() {}
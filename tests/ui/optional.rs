@@ -0,0 +1,52 @@
+use bitwrap::*;
+
+
+#[derive(BitWrap)]
+struct ConditionNotAligned {
+    #[bitfield(8)]
+    flags: u8,
+    #[bitfield(4, if = flags != 0)]
+    value: Option<u8>,
+}
+
+
+#[derive(BitWrap)]
+struct ConditionWithoutOption {
+    #[bitfield(8)]
+    flags: u8,
+    #[bitfield(8, if = flags != 0)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+struct ConditionVirtualOnly {
+    #[bitfield(8)]
+    flags: u8,
+    #[bitfield(8, name = _reserved, value = 0, if = flags != 0)]
+    #[bitfield(8)]
+    value: u8,
+}
+
+
+#[derive(BitWrap)]
+struct ConditionWithLength {
+    #[bitfield(8, name = len, length_of = data, if = true)]
+    #[bitfield]
+    data: Vec<u8>,
+}
+
+
+#[derive(BitWrap)]
+struct LengthFromCondition {
+    #[bitfield(8)]
+    flags: u8,
+    #[bitfield(8, name = len, length_of = (value, tail))]
+    #[bitfield(8, if = flags != 0)]
+    value: Option<u8>,
+    #[bitfield(8)]
+    tail: u8,
+}
+
+
+fn main() {}
@@ -0,0 +1,23 @@
+use bitwrap::*;
+
+
+#[derive(BitWrap)]
+#[bitwrap(size = 188)]
+struct SizeMismatch {
+    #[bitfield(8)]
+    sync: u8,
+    #[bitfield]
+    payload: [u8; 186],
+}
+
+
+#[derive(BitWrap)]
+#[bitwrap(size = 4)]
+struct SizeDynamic {
+    #[bitfield(8, name = data_len, value = self.data.len())]
+    #[bitfield(data_len)]
+    data: Vec<u8>,
+}
+
+
+fn main() {}
@@ -0,0 +1,42 @@
+use bitwrap::*;
+
+
+// field value could not be packed without error
+#[derive(BitWrap)]
+struct Overflow {
+    #[bitfield(4)]
+    a: u8,
+    #[bitfield(4)]
+    b: u8,
+}
+
+
+// virtual field value could not be packed without error
+#[derive(BitWrap)]
+struct VirtualOverflow {
+    #[bitfield(4, name = x, value = self.a)]
+    #[bitfield(4)]
+    b: u8,
+    a: u8,
+}
+
+
+// literal value is out of the field size
+#[derive(BitWrap)]
+struct LiteralOverflow {
+    #[bitfield(4, name = x, value = 0x1F)]
+    #[bitfield(4, truncate)]
+    b: u8,
+}
+
+
+fn main() {
+    let value = Overflow { a: 0x1F, b: 0 };
+    let _ = value.to_bytes();
+
+    let value = VirtualOverflow { a: 0x1F, b: 0 };
+    let _ = value.to_bytes();
+
+    let value = LiteralOverflow { b: 0 };
+    let _ = value.to_bytes();
+}
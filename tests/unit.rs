@@ -0,0 +1,162 @@
+use bitwrap::*;
+
+mod common;
+
+
+#[test]
+fn test_unit_words32() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Header {
+        #[bitfield(4, name = _version, value = 4)]
+        #[bitfield(4, name = ihl, unit = words32, adjust = -1, length_of = (tos, options))]
+        #[bitfield(8)] tos: u8,
+        #[bitfield(16)] total_length: u16,
+        #[bitfield] options: Vec<u8>,
+    }
+
+    let header = Header {
+        tos: 0x10,
+        total_length: 0x20,
+        options: vec![1, 2, 3, 4],
+    };
+
+    const DATA: &[u8] = &[0x42, 0x10, 0x00, 0x20, 0x01, 0x02, 0x03, 0x04];
+
+    assert_eq!(header.pack_to_vec().unwrap(), DATA);
+    assert_eq!(Header::unpack_from(DATA), Ok((header, DATA.len())));
+
+    // options are not aligned to 32-bit words
+    let header = Header {
+        tos: 0,
+        total_length: 0,
+        options: vec![1, 2],
+    };
+    let error = header.pack_to_vec().unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.field_name(), Some("ihl"));
+
+    // length is less than fixed header
+    let error = Header::unpack_from(&[0x40, 0x10, 0x00, 0x20]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.field_name(), Some("ihl"));
+
+    common::unpack_random::<Header>(&[DATA]);
+}
+
+
+#[test]
+fn test_unit_bits() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Packet {
+        #[bitfield(16, name = data_bits, value = self.data.len())]
+        #[bitfield(data_bits, unit = bits)]
+        data: Vec<u8>,
+    }
+
+    const DATA: &[u8] = &[0x00, 0x10, 0xAB, 0xCD];
+
+    let (packet, result) = Packet::unpack_from(DATA).unwrap();
+    assert_eq!(result, DATA.len());
+    assert_eq!(packet.data, vec![0xAB, 0xCD]);
+    assert_eq!(packet.pack_to_vec().unwrap(), DATA);
+
+    let error = Packet::unpack_from(&[0x00, 0x0C, 0xAB, 0xCD]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.field_name(), Some("data"));
+    assert_eq!(error.byte_offset(), 2);
+
+    common::unpack_random::<Packet>(&[DATA]);
+}
+
+
+#[test]
+fn test_unit_length_value() {
+    // value of the virtual field is in bytes, length includes one more word
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Packet {
+        #[bitfield(8, name = n, value = self.data.len())]
+        #[bitfield(n, unit = words32, adjust = -4)]
+        data: Vec<u8>,
+    }
+
+    let packet = Packet {
+        data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+    };
+
+    const DATA: &[u8] = &[0x03, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08];
+
+    assert_eq!(packet.packed_len(), DATA.len());
+    assert_eq!(packet.pack_to_vec().unwrap(), DATA);
+    assert_eq!(Packet::unpack_from(DATA), Ok((packet, DATA.len())));
+
+    // data is not aligned to 32-bit words
+    let packet = Packet {
+        data: vec![1, 2],
+    };
+    let error = packet.pack_to_vec().unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.field_name(), Some("n"));
+
+    common::unpack_random::<Packet>(&[DATA]);
+}
+
+
+#[test]
+fn test_unit_elements() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Point {
+        #[bitfield(8)] x: u8,
+        #[bitfield(8)] y: u8,
+    }
+
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Shape {
+        #[bitfield(8, name = points_len, value = self.points.packed_len())]
+        #[bitfield(points_len, unit = elements)]
+        points: Vec<Point>,
+        #[bitfield(8, name = tail_len, length_of = tail, unit = 3)]
+        #[bitfield]
+        tail: Vec<u8>,
+    }
+
+    let shape = Shape {
+        points: vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }],
+        tail: vec![5, 6, 7, 8, 9, 10],
+    };
+
+    const DATA: &[u8] = &[0x02, 0x01, 0x02, 0x03, 0x04, 0x02, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A];
+
+    assert_eq!(shape.pack_to_vec().unwrap(), DATA);
+    assert_eq!(Shape::unpack_from(DATA), Ok((shape, DATA.len())));
+
+    common::unpack_random::<Shape>(&[DATA]);
+}
+
+
+#[test]
+fn test_unit_overflow() {
+    #[derive(Debug, BitWrap)]
+    struct Packet {
+        #[bitfield(128, name = len, length_of = data, unit = words32)]
+        #[bitfield]
+        data: Vec<u8>,
+    }
+
+    // length in bytes out of the usize range
+    let mut data = [0xFF; 17];
+    data[0] = 0x7F;
+    let error = Packet::unpack_from(&data).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.field_name(), Some("len"));
+
+    #[derive(Debug, BitWrap)]
+    struct Long {
+        #[bitfield(64, name = len, length_of = data)]
+        #[bitfield]
+        data: Vec<u8>,
+    }
+
+    let error = Long::unpack_from(&[0xFF; 9]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::BufferTooShort { needed: usize::MAX });
+    assert_eq!(error.field_name(), Some("data"));
+}
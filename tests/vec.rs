@@ -1,5 +1,7 @@
 use bitwrap::*;
 
+mod common;
+
 
 #[test]
 fn test_vec() {
@@ -23,6 +25,8 @@ fn test_vec() {
 
     assert_eq!(result, DATA.len());
     assert_eq!(&buffer[.. result], DATA);
+
+    common::unpack_random::<Packet>(&[DATA]);
 }
 
 
@@ -38,10 +42,31 @@ fn test_vec_overflow() {
     const DATA: &[u8] = &[0xFF, 0xF0, 0x9F, 0xA6, 0x80];
 
     let mut packet = Packet::default();
-    assert_eq!(packet.unpack(DATA), Err(BitWrapError));
+    assert_eq!(
+        packet.unpack(DATA),
+        Err(BitWrapError::new(BitWrapErrorKind::BufferTooShort { needed: 255 })
+            .at_field("Packet", "data", 8))
+    );
 
     packet.data.extend_from_slice(&[0xF0, 0x9F, 0xA6, 0x80]);
 
     let mut buffer: [u8; 4] = [0; 4];
-    assert_eq!(packet.pack(&mut buffer), Err(BitWrapError));
+    let error = packet.pack(&mut buffer).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::BufferTooShort { needed: 5 });
+    assert_eq!(error.byte_offset(), 1);
+}
+
+
+#[test]
+fn test_vec_empty_item() {
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Empty {}
+
+    // item without bytes is an error instead of endless loop
+    let error = Vec::<Empty>::unpack_from(&[0x00]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
+    assert_eq!(error.byte_offset(), 0);
+
+    let error = <Vec<Empty> as BitWrapCount>::unpack_count(usize::MAX, &[]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::InvalidValue);
 }
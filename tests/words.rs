@@ -0,0 +1,161 @@
+use bitwrap::*;
+
+mod common;
+
+
+#[derive(Debug, PartialEq, BitWrap)]
+struct TsHeader {
+    #[bitfield(8, name = _sync, value = 0x47)]
+    #[bitfield(1)]
+    error: bool,
+    #[bitfield(1)]
+    pusi: bool,
+    #[bitfield(1)]
+    priority: bool,
+    #[bitfield(13)]
+    pid: u16,
+    #[bitfield(2)]
+    scrambling: u8,
+    #[bitfield(2)]
+    adaptation: u8,
+    #[bitfield(4)]
+    cc: u8,
+}
+
+
+#[test]
+fn test_words() {
+    let header = TsHeader {
+        error: false,
+        pusi: true,
+        priority: false,
+        pid: 0x1FFF,
+        scrambling: 0,
+        adaptation: 1,
+        cc: 0x0A,
+    };
+
+    const DATA: &[u8] = &[0x47, 0x5F, 0xFF, 0x1A];
+
+    let mut buffer = [0xFF; 4];
+    assert_eq!(header.pack(&mut buffer), Ok(4));
+    assert_eq!(buffer, DATA);
+    assert_eq!(TsHeader::unpack_from(DATA), Ok((header, DATA.len())));
+
+    let error = TsHeader::unpack_from(&[0x47, 0x5F]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::BufferTooShort { needed: 3 });
+    assert_eq!(error.field_name(), Some("pid"));
+    assert_eq!(error.bit_offset(), 11);
+
+    let error = TsHeader::unpack_from(&[0x47, 0x5F, 0xFF]).unwrap_err();
+    assert_eq!(error.kind(), BitWrapErrorKind::BufferTooShort { needed: 4 });
+    assert_eq!(error.field_name(), Some("scrambling"));
+    assert_eq!(error.bit_offset(), 24);
+
+    common::unpack_random::<TsHeader>(&[DATA]);
+}
+
+
+#[test]
+fn test_words_wide() {
+    // 8 + 64 + 16 bits in the two words, little-endian field is aligned
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Packet {
+        #[bitfield(4)]
+        version: u8,
+        #[bitfield(36)]
+        time: u64,
+        #[bitfield(4)]
+        flags: u8,
+        #[bitfield(4, name = _reserved, value = 0)]
+        #[bitfield(32, endian = little)]
+        id: u32,
+        #[bitfield(12, truncate)]
+        tail: u16,
+        #[bitfield(4)]
+        last: u8,
+    }
+
+    let packet = Packet {
+        version: 0x0A,
+        time: 0x1_2345_6789,
+        flags: 0x0F,
+        id: 0x0102_0304,
+        tail: 0xABC,
+        last: 0x05,
+    };
+
+    const DATA: &[u8] = &[
+        0xA1, 0x23, 0x45, 0x67, 0x89, 0xF0,
+        0x04, 0x03, 0x02, 0x01,
+        0xAB, 0xC5,
+    ];
+
+    assert_eq!(packet.pack_to_vec().unwrap(), DATA);
+    assert_eq!(Packet::unpack_from(DATA), Ok((packet, DATA.len())));
+
+    common::unpack_random::<Packet>(&[DATA]);
+
+    // truncated value in the word
+    let packet = Packet {
+        version: 0,
+        time: 0,
+        flags: 0,
+        id: 0,
+        tail: 0xFABC,
+        last: 0,
+    };
+
+    let data = packet.pack_to_vec().unwrap();
+    assert_eq!(&data[10 ..], &[0xAB, 0xC0]);
+}
+
+
+#[test]
+fn test_words_fallback() {
+    // little-endian field in the middle of the byte is packed by bytes
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Packet {
+        #[bitfield(4)]
+        version: u8,
+        #[bitfield(12, endian = little)]
+        value: u16,
+    }
+
+    let packet = Packet {
+        version: 0x0A,
+        value: 0x123,
+    };
+
+    let data = packet.pack_to_vec().unwrap();
+    assert_eq!(Packet::unpack_from(&data), Ok((packet, 2)));
+
+    // 128-bit field without byte boundary in the word
+    #[derive(Debug, PartialEq, BitWrap)]
+    struct Wide {
+        #[bitfield(4)]
+        head: u8,
+        #[bitfield(120)]
+        value: u128,
+        #[bitfield(8)]
+        tail: u8,
+        #[bitfield(4)]
+        last: u8,
+    }
+
+    let wide = Wide {
+        head: 0x01,
+        value: 0x0023_4567_89AB_CDEF_0123_4567_89AB_CDEF,
+        tail: 0xFF,
+        last: 0x0E,
+    };
+
+    let data = wide.pack_to_vec().unwrap();
+    assert_eq!(data.len(), 17);
+    assert_eq!(data[0], 0x12);
+    assert_eq!(data[15], 0xFF);
+    assert_eq!(data[16], 0xFE);
+    assert_eq!(Wide::unpack_from(&data), Ok((wide, 17)));
+
+    common::unpack_random::<Wide>(&[&data]);
+}